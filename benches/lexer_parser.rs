@@ -0,0 +1,65 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use monkey_rs::lexer::Lexer;
+use monkey_rs::parser::Parser;
+
+/// Builds `n` let statements, each rebinding `x` to a `depth`-deep nested
+/// arithmetic expression over its previous value, e.g. `let x = (((x + 1) - 2) * 3);`
+fn generate_program(n: usize, depth: usize) -> String {
+    let mut source = String::from("let x = 0;\n");
+    for _ in 0..n {
+        let mut expression = String::from("x");
+        for d in 0..depth {
+            let operator = ["+", "-", "*", "/"][d % 4];
+            expression = format!("({} {} {})", expression, operator, d + 1);
+        }
+        source.push_str(&format!("let x = {};\n", expression));
+    }
+    source
+}
+
+/// Lexes 1,000 statements of depth 8 (roughly 55KB of source)
+fn bench_lexer(c: &mut Criterion) {
+    let source = generate_program(1_000, 8);
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("1000x8", |b| b.iter(|| Lexer::new(black_box(&source)).count()));
+    group.finish();
+}
+
+/// Lexes 1,000 copies of a function that is mostly keywords and identifiers
+fn bench_lexer_identifiers(c: &mut Criterion) {
+    let source = "let add = fn(left, right) { if (left) { return left + right; } else { return right; } };\n"
+        .repeat(1_000);
+    let mut group = c.benchmark_group("lexer_identifiers");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("1000", |b| b.iter(|| Lexer::new(black_box(&source)).count()));
+    group.finish();
+}
+
+/// Parses 1,000 statements of depth 8, including the lexing it drives
+fn bench_parser(c: &mut Criterion) {
+    let source = generate_program(1_000, 8);
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("1000x8", |b| {
+        b.iter(|| Parser::new(Lexer::new(black_box(&source))).parse_program().unwrap())
+    });
+    group.finish();
+}
+
+/// Parses 100 statements at nesting depths of 4, 16 and 64 to show how
+/// deeply nested expressions scale
+fn bench_parser_nesting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser_nesting");
+    for depth in [4, 16, 64] {
+        let source = generate_program(100, depth);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &source, |b, source| {
+            b.iter(|| Parser::new(Lexer::new(black_box(source))).parse_program().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer, bench_lexer_identifiers, bench_parser, bench_parser_nesting);
+criterion_main!(benches);
@@ -4,11 +4,15 @@ use std::fmt::{Display, Formatter};
 use crate::token::Token;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type", content = "value"))]
 pub enum Statement {
     Let{ ident: Identifier, value: Expression },
     Return{ value: Expression },
-    Expression{ value: Expression },
+    /// `semicolon` records a trailing `;`, which discards the value when the
+    /// statement ends a block
+    Expression{ value: Expression, semicolon: bool },
     Block{ statements: Vec<Statement> },
+    While{ condition: Expression, body: Box<Statement> },
 }
 
 impl Display for Statement {
@@ -16,20 +20,22 @@ impl Display for Statement {
         match self {
             Statement::Let { ident, value } => write!(f, "{} {} = {};", Token::Let, ident.value, value),
             Statement::Return { value } => write!(f, "{} {};", Token::Return, value),
-            Statement::Expression { value } => write!(f, "{}", value),
+            Statement::Expression { value, semicolon: true } => write!(f, "{};", value),
+            Statement::Expression { value, semicolon: false } => write!(f, "{}", value),
             Statement::Block { statements } => {
-                write!(f, "{{")?;
-                for statement in statements {
-                    write!(f, "{}", statement)?;
+                if statements.is_empty() {
+                    return write!(f, "{{}}");
                 }
-                write!(f, "}}")?;
-                Ok(())
+                let statements: Vec<String> = statements.iter().map(|s| s.to_string()).collect();
+                write!(f, "{{ {} }}", statements.join(" "))
             }
+            Statement::While { condition, body } => write!(f, "{} {} {}", Token::While, condition.parenthesized(), body),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Identifier {
     pub value: String,
 }
@@ -45,11 +51,42 @@ impl Identifier {
     }
 }
 
+/// The token for an assignment, `=` or a compound one like `+=` when it
+/// combines the old value with `operator`
+pub fn assignment_operator(operator: Option<&Token>) -> String {
+    match operator {
+        Some(operator) => format!("{}{}", operator, Token::Assign),
+        None => Token::Assign.to_string(),
+    }
+}
+
+/// A function parameter and its default value, if it has one
+pub type Parameter = (Identifier, Option<Expression>);
+
+/// Formats parameters as they are written in source, e.g. `x, y = 10`
+pub fn format_parameters(parameters: &[Parameter]) -> String {
+    let parameters: Vec<String> = parameters
+        .iter()
+        .map(|(name, default)| match default {
+            Some(default) => format!("{} {} {}", name.value, Token::Assign, default),
+            None => name.value.clone(),
+        })
+        .collect();
+    parameters.join(", ")
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type", content = "value"))]
 pub enum Expression {
     Identifier(Identifier),
     IntegerLiteral(i64),
+    FloatLiteral(f64),
+    StringLiteral(String),
+    CharLiteral(char),
+    Array(Vec<Expression>),
+    Hash(Vec<(Expression, Expression)>),
     Boolean(bool),
+    Null,
     Prefix {
         operator: Token,
         right: Box<Expression>,
@@ -64,7 +101,40 @@ pub enum Expression {
         consequence: Box<Statement>,
         alternative: Option<Box<Statement>>,
     },
-    
+    FunctionLiteral {
+        parameters: Vec<Parameter>,
+        body: Box<Statement>,
+    },
+    Call {
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+    },
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// `left[start:end]`, where either bound may be left out
+    Slice {
+        left: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
+    Assign {
+        name: Identifier,
+        value: Box<Expression>,
+    },
+    /// `left[index] = value`, or with `operator` set a compound assignment
+    /// such as `left[index] += value`, which reads the target only once
+    IndexAssign {
+        left: Box<Expression>,
+        index: Box<Expression>,
+        operator: Option<Token>,
+        value: Box<Expression>,
+    },
+    MacroLiteral {
+        parameters: Vec<Identifier>,
+        body: Box<Statement>,
+    },
 }
 
 impl Display for Expression {
@@ -72,30 +142,174 @@ impl Display for Expression {
         match self {
             Expression::Identifier(ident) => write!(f, "{}", ident.value),
             Expression::IntegerLiteral(value) => write!(f, "{}", value),
+            Expression::FloatLiteral(value) => write!(f, "{:?}", value),
+            Expression::StringLiteral(value) => write!(f, "{}", value),
+            Expression::CharLiteral(value) => write!(f, "{}", value),
+            Expression::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Expression::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
             Expression::Boolean(value) => write!(f, "{}", value),
+            Expression::Null => write!(f, "{}", Token::Null),
             Expression::Prefix { operator, right } => write!(f, "({}{})", operator, right),
             Expression::Infix { left, operator, right } => write!(f, "({} {} {})", left, operator, right),
             Expression::If { condition, consequence, alternative } => {
-                write!(f, "{} {} ", Token::If, condition)?;
+                write!(f, "{} {} ", Token::If, condition.parenthesized())?;
                 write!(f, "{}", consequence)?;
                 if let Some(alt) = alternative {
                     write!(f, " {} {}", Token::Else, alt)?;
                 }
                 Ok(())
             }
+            Expression::FunctionLiteral { parameters, body } => {
+                write!(f, "{}({}) {}", Token::Function, format_parameters(parameters), body)
+            }
+            Expression::Call { function, arguments } => {
+                let args: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", function, args.join(", "))
+            }
+            Expression::Index { left, index } => write!(f, "({}[{}])", left, index),
+            Expression::Slice { left, start, end } => {
+                write!(f, "({}[", left)?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "])")
+            }
+            Expression::Assign { name, value } => write!(f, "{} {} {}", name.value, Token::Assign, value),
+            Expression::IndexAssign { left, index, operator, value } => {
+                write!(f, "{}[{}] {} {}", left, index, assignment_operator(operator.as_ref()), value)
+            }
+            Expression::MacroLiteral { parameters, body } => {
+                let params: Vec<&str> = parameters.iter().map(|p| p.value.as_str()).collect();
+                write!(f, "{}({}) {}", Token::Macro, params.join(", "), body)
+            }
+        }
+    }
+}
+
+impl Statement {
+    /// Rebuilds the statement, passing each expression in it through `modifier`
+    pub fn modify<F: FnMut(Expression) -> Expression>(self, modifier: &mut F) -> Statement {
+        match self {
+            Statement::Let { ident, value } => Statement::Let { ident, value: value.modify(modifier) },
+            Statement::Return { value } => Statement::Return { value: value.modify(modifier) },
+            Statement::Expression { value, semicolon } => Statement::Expression { value: value.modify(modifier), semicolon },
+            Statement::Block { statements } => Statement::Block {
+                statements: statements.into_iter().map(|s| s.modify(modifier)).collect(),
+            },
+            Statement::While { condition, body } => Statement::While {
+                condition: condition.modify(modifier),
+                body: Box::new(body.modify(modifier)),
+            },
+        }
+    }
+}
+
+impl Expression {
+    /// Rebuilds the expression bottom-up: children are modified first, then
+    /// the node itself is passed through `modifier`
+    pub fn modify<F: FnMut(Expression) -> Expression>(self, modifier: &mut F) -> Expression {
+        let modified = match self {
+            Expression::Array(elements) => {
+                Expression::Array(elements.into_iter().map(|e| e.modify(modifier)).collect())
+            }
+            Expression::Hash(pairs) => Expression::Hash(
+                pairs.into_iter().map(|(k, v)| (k.modify(modifier), v.modify(modifier))).collect(),
+            ),
+            Expression::Prefix { operator, right } => Expression::Prefix {
+                operator,
+                right: Box::new(right.modify(modifier)),
+            },
+            Expression::Infix { left, operator, right } => Expression::Infix {
+                left: Box::new(left.modify(modifier)),
+                operator,
+                right: Box::new(right.modify(modifier)),
+            },
+            Expression::If { condition, consequence, alternative } => Expression::If {
+                condition: Box::new(condition.modify(modifier)),
+                consequence: Box::new(consequence.modify(modifier)),
+                alternative: alternative.map(|alt| Box::new(alt.modify(modifier))),
+            },
+            Expression::FunctionLiteral { parameters, body } => Expression::FunctionLiteral {
+                parameters: parameters
+                    .into_iter()
+                    .map(|(name, default)| (name, default.map(|d| d.modify(modifier))))
+                    .collect(),
+                body: Box::new(body.modify(modifier)),
+            },
+            Expression::MacroLiteral { parameters, body } => Expression::MacroLiteral {
+                parameters,
+                body: Box::new(body.modify(modifier)),
+            },
+            Expression::Call { function, arguments } => Expression::Call {
+                function: Box::new(function.modify(modifier)),
+                arguments: arguments.into_iter().map(|a| a.modify(modifier)).collect(),
+            },
+            Expression::Index { left, index } => Expression::Index {
+                left: Box::new(left.modify(modifier)),
+                index: Box::new(index.modify(modifier)),
+            },
+            Expression::Slice { left, start, end } => Expression::Slice {
+                left: Box::new(left.modify(modifier)),
+                start: start.map(|s| Box::new(s.modify(modifier))),
+                end: end.map(|e| Box::new(e.modify(modifier))),
+            },
+            Expression::Assign { name, value } => Expression::Assign {
+                name,
+                value: Box::new(value.modify(modifier)),
+            },
+            Expression::IndexAssign { left, index, operator, value } => Expression::IndexAssign {
+                left: Box::new(left.modify(modifier)),
+                index: Box::new(index.modify(modifier)),
+                operator,
+                value: Box::new(value.modify(modifier)),
+            },
+            leaf => leaf,
+        };
+        modifier(modified)
+    }
+
+    /// Display form wrapped in parentheses, unless `Display` already wraps it,
+    /// as needed for `if` and `while` conditions
+    fn parenthesized(&self) -> String {
+        match self {
+            Expression::Prefix { .. }
+            | Expression::Infix { .. }
+            | Expression::Index { .. }
+            | Expression::Slice { .. } => self.to_string(),
+            _ => format!("({})", self),
         }
     }
 }
 
-#[derive(Debug)]
+/// Byte offsets `(start, end)` of a node in the source it was parsed from
+pub type Span = (usize, usize);
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Program { 
     pub statements: Vec<Statement>,
+    /// The span of each statement added through [`Program::add_spanned_statement`],
+    /// or `None` for one added without, in step with `statements`
+    spans: Vec<Option<Span>>,
 }
 
+/// Every statement is written on its own line, including the last, so a
+/// program prints as a newline-terminated file. `Statement` itself never adds
+/// a newline; only its own `;` terminator.
 impl Display for Program {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         for statement in &self.statements {
-            write!(f, "{}", statement)?;
+            writeln!(f, "{}", statement)?;
         }
         Ok(())
     }
@@ -103,12 +317,47 @@ impl Display for Program {
 
 impl Program {
     pub fn new() -> Self {
-        Self {
-            statements: Vec::new(),
-        }
+        Self::default()
     }
     pub fn add_statement(&mut self, statement: Statement) {
         self.statements.push(statement);
+        self.spans.push(None);
+    }
+
+    pub fn add_spanned_statement(&mut self, statement: Statement, span: Span) {
+        self.statements.push(statement);
+        self.spans.push(Some(span));
+    }
+
+    /// Span of the top-level statement at `index`, if it was added with one
+    pub fn span(&self, index: usize) -> Option<Span> {
+        self.spans.get(index).copied().flatten()
+    }
+
+    /// Removes every statement, returning each with its span
+    pub fn take_statements(&mut self) -> Vec<(Statement, Option<Span>)> {
+        let spans = std::mem::take(&mut self.spans);
+        let spans = spans.into_iter().chain(std::iter::repeat(None));
+        std::mem::take(&mut self.statements).into_iter().zip(spans).collect()
+    }
+
+    /// Rebuilds the program, passing each expression in it through `modifier`
+    pub fn modify<F: FnMut(Expression) -> Expression>(self, modifier: &mut F) -> Program {
+        Program {
+            statements: self.statements.into_iter().map(|s| s.modify(modifier)).collect(),
+            spans: self.spans,
+        }
+    }
+
+    /// Formats the program as indented, minimally parenthesized source
+    pub fn to_source(&self) -> String {
+        crate::pretty::to_source(self)
+    }
+
+    /// Serializes the program as JSON, tagging each node with its variant under `"type"`
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("AST nodes always serialize")
     }
 }
 
@@ -116,6 +365,7 @@ impl Program {
 #[cfg(test)]
 mod tests {
     use super::{Expression, Identifier, Program, Statement};
+    use crate::token::Token;
     #[test]
     fn test_display_program() {
         let program = Program {
@@ -132,7 +382,114 @@ mod tests {
                     value: Expression::Identifier(Identifier { value: "anotherVar".to_string() }),
                 },
             ],
+            spans: vec![],
         };
         assert_eq!(format!("{}", program), "let myVar = 5;\nlet anotherVar = myVar;\nreturn anotherVar;\n");
     }
+
+    #[test]
+    fn test_modify() {
+        let one = || Expression::IntegerLiteral(1);
+        let two = || Expression::IntegerLiteral(2);
+        let mut turn_one_into_two = |expression| match expression {
+            Expression::IntegerLiteral(1) => Expression::IntegerLiteral(2),
+            other => other,
+        };
+        let test_cases = vec![
+            (one(), two()),
+            (
+                Expression::Infix { left: Box::new(one()), operator: Token::Plus, right: Box::new(two()) },
+                Expression::Infix { left: Box::new(two()), operator: Token::Plus, right: Box::new(two()) },
+            ),
+            (
+                Expression::Prefix { operator: Token::Minus, right: Box::new(one()) },
+                Expression::Prefix { operator: Token::Minus, right: Box::new(two()) },
+            ),
+            (
+                Expression::Index { left: Box::new(one()), index: Box::new(one()) },
+                Expression::Index { left: Box::new(two()), index: Box::new(two()) },
+            ),
+            (
+                Expression::If {
+                    condition: Box::new(one()),
+                    consequence: Box::new(Statement::Block { statements: vec![Statement::Expression { value: one(), semicolon: false }] }),
+                    alternative: Some(Box::new(Statement::Block { statements: vec![Statement::Return { value: one() }] })),
+                },
+                Expression::If {
+                    condition: Box::new(two()),
+                    consequence: Box::new(Statement::Block { statements: vec![Statement::Expression { value: two(), semicolon: false }] }),
+                    alternative: Some(Box::new(Statement::Block { statements: vec![Statement::Return { value: two() }] })),
+                },
+            ),
+            (
+                Expression::FunctionLiteral {
+                    parameters: vec![],
+                    body: Box::new(Statement::Block { statements: vec![Statement::Expression { value: one(), semicolon: false }] }),
+                },
+                Expression::FunctionLiteral {
+                    parameters: vec![],
+                    body: Box::new(Statement::Block { statements: vec![Statement::Expression { value: two(), semicolon: false }] }),
+                },
+            ),
+            (Expression::Array(vec![one(), one()]), Expression::Array(vec![two(), two()])),
+            (Expression::Hash(vec![(one(), one())]), Expression::Hash(vec![(two(), two())])),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(input.modify(&mut turn_one_into_two), expected);
+        }
+
+        let program = Program {
+            statements: vec![Statement::Let { ident: Identifier { value: "x".to_string() }, value: one() }],
+            spans: vec![Some((0, 10))],
+        };
+        let expected = vec![Statement::Let { ident: Identifier { value: "x".to_string() }, value: two() }];
+        let program = program.modify(&mut turn_one_into_two);
+        assert_eq!(program.statements, expected);
+        assert_eq!(program.span(0), Some((0, 10)));
+    }
+
+    #[test]
+    fn test_spans_stay_in_step_with_statements() {
+        let statement = |value| Statement::Expression { value: Expression::IntegerLiteral(value), semicolon: true };
+        let mut program = Program::new();
+        program.add_spanned_statement(statement(1), (0, 2));
+        program.add_statement(statement(2));
+        program.add_spanned_statement(statement(3), (6, 8));
+
+        assert_eq!(program.span(0), Some((0, 2)));
+        assert_eq!(program.span(1), None);
+        assert_eq!(program.span(2), Some((6, 8)));
+        assert_eq!(program.span(3), None);
+
+        let taken = program.take_statements();
+        let spans: Vec<_> = taken.into_iter().map(|(_, span)| span).collect();
+        assert_eq!(spans, vec![Some((0, 2)), None, Some((6, 8))]);
+        assert!(program.statements.is_empty());
+        assert_eq!(program.span(0), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_program_to_json() {
+        let program = Program {
+            statements: vec![Statement::Let {
+                ident: Identifier { value: "x".to_string() },
+                value: Expression::Infix {
+                    left: Box::new(Expression::IntegerLiteral(1)),
+                    operator: Token::Plus,
+                    right: Box::new(Expression::Identifier(Identifier { value: "y".to_string() })),
+                },
+            }],
+            spans: vec![],
+        };
+        let json: serde_json::Value = serde_json::from_str(&program.to_json()).unwrap();
+        let statement = &json["statements"][0];
+
+        assert_eq!(statement["type"], "Let");
+        assert_eq!(statement["value"]["ident"]["value"], "x");
+        assert_eq!(statement["value"]["value"]["type"], "Infix");
+        assert_eq!(statement["value"]["value"]["value"]["operator"], "Plus");
+        assert_eq!(statement["value"]["value"]["value"]["left"], serde_json::json!({ "type": "IntegerLiteral", "value": 1 }));
+        assert_eq!(statement["value"]["value"]["value"]["right"]["value"]["value"], "y");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use crate::evaluator::Evaluator;
+use crate::object::{HashKey, Object};
+
+pub type BuiltinFn = fn(&mut Evaluator, Vec<Object>) -> Object;
+
+/// A function supplied by a program embedding the interpreter
+pub type HostFn = fn(Vec<Object>) -> Object;
+
+/// The native code behind a built-in
+#[derive(Clone, Copy)]
+pub enum BuiltinFunc {
+    /// One of the interpreter's own, which can call back into the evaluator
+    Native(BuiltinFn),
+    /// One registered with [`Builtins::register`]
+    Host(HostFn),
+}
+
+/// A named native function callable from Monkey code
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: String,
+    pub func: BuiltinFunc,
+}
+
+impl Builtin {
+    pub fn call(&self, evaluator: &mut Evaluator, args: Vec<Object>) -> Object {
+        match self.func {
+            BuiltinFunc::Native(func) => func(evaluator, args),
+            BuiltinFunc::Host(func) => func(args),
+        }
+    }
+}
+
+// Function pointers have no meaningful identity, so built-ins compare by name
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Debug for Builtin {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+/// The most elements `range` will build, so a huge bound errors instead of
+/// exhausting memory
+const MAX_RANGE_LEN: i128 = 1_000_000;
+
+const BUILTINS: &[(&str, BuiltinFn)] = &[
+    ("len", len),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("puts", puts),
+    ("type", type_of),
+    ("str", str),
+    ("int", int),
+    ("range", range),
+    ("map", map),
+    ("filter", filter),
+    ("reduce", reduce),
+    ("split", split),
+    ("join", join),
+    ("keys", keys),
+    ("values", values),
+    ("contains", contains),
+    ("clone", clone),
+];
+
+/// Looks up a built-in function by name
+pub fn lookup(name: &str) -> Option<Object> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|&(name, func)| Object::Builtin(Builtin { name: name.to_string(), func: BuiltinFunc::Native(func) }))
+}
+
+/// The built-ins a program can call: the standard ones plus any the host
+/// has registered, which take precedence when the names clash
+#[derive(Clone, Default)]
+pub struct Builtins {
+    host: HashMap<String, HostFn>,
+}
+
+impl Builtins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `f` callable from Monkey code as `name`
+    pub fn register(&mut self, name: &str, f: HostFn) {
+        self.host.insert(name.to_string(), f);
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Object> {
+        match self.host.get(name) {
+            Some(&func) => Some(Object::Builtin(Builtin { name: name.to_string(), func: BuiltinFunc::Host(func) })),
+            None => lookup(name),
+        }
+    }
+}
+
+/// Names of every built-in function
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|(name, _)| *name)
+}
+
+pub fn wrong_arguments(expected: usize, got: usize) -> Object {
+    Object::Error(format!("wrong number of arguments: expected {}, got {}", expected, got))
+}
+
+/// Checks that `function` can be called, naming the builtin that was given it otherwise
+fn expect_callable(builtin: &str, function: &Object) -> Option<Object> {
+    match function {
+        Object::Function { .. } | Object::Builtin(_) => None,
+        other => Some(Object::Error(format!(
+            "argument to `{}` must be FUNCTION, got {}",
+            builtin,
+            other.type_name()
+        ))),
+    }
+}
+
+fn len(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::String(value) => Object::Integer(value.chars().count() as i64),
+        Object::Array(elements) => Object::Integer(elements.len() as i64),
+        other => Object::Error(format!("argument to `len` not supported, got {}", other.type_name())),
+    }
+}
+
+fn first(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::Array(elements) => elements.first().cloned().unwrap_or(Object::Null),
+        other => Object::Error(format!("argument to `first` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+fn last(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::Array(elements) => elements.last().cloned().unwrap_or(Object::Null),
+        other => Object::Error(format!("argument to `last` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+/// Returns a new array without the first element, or `null` for an empty array
+fn rest(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::Array(elements) if elements.is_empty() => Object::Null,
+        Object::Array(elements) => Object::Array(elements[1..].to_vec()),
+        other => Object::Error(format!("argument to `rest` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+/// Returns a new array with the element appended, leaving the original untouched
+fn push(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return wrong_arguments(2, args.len());
+    }
+    match &args[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.clone();
+            elements.push(args[1].clone());
+            Object::Array(elements)
+        }
+        other => Object::Error(format!("argument to `push` must be ARRAY, got {}", other.type_name())),
+    }
+}
+
+/// Prints each argument on its own line to the evaluator's output
+fn puts(evaluator: &mut Evaluator, args: Vec<Object>) -> Object {
+    for arg in args {
+        if let Err(e) = writeln!(evaluator.output(), "{}", arg) {
+            return Object::Error(format!("could not write output: {}", e));
+        }
+    }
+    Object::Null
+}
+
+/// Returns the name of the argument's runtime type, e.g. `"INTEGER"`
+fn type_of(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    Object::String(args[0].type_name().to_string())
+}
+
+/// Converts the argument to a string, the way `puts` would print it
+fn str(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    Object::String(args[0].to_string())
+}
+
+/// Parses a string as an integer, or converts a boolean to 0 or 1
+fn int(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::Integer(value) => Object::Integer(*value),
+        Object::Boolean(value) => Object::Integer(*value as i64),
+        Object::String(value) => match value.trim().parse() {
+            Ok(value) => Object::Integer(value),
+            Err(_) => Object::Error(format!("could not parse '{}' as INTEGER", value)),
+        },
+        other => Object::Error(format!("argument to `int` not supported, got {}", other.type_name())),
+    }
+}
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`: the integers
+/// from `start` up to but excluding `end`, `step` apart
+fn range(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    let mut bounds = Vec::with_capacity(args.len());
+    for arg in &args {
+        match arg {
+            Object::Integer(value) => bounds.push(*value),
+            other => return Object::Error(format!("argument to `range` must be INTEGER, got {}", other.type_name())),
+        }
+    }
+    let (start, end, step) = match bounds[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => {
+            return Object::Error(format!(
+                "wrong number of arguments: expected 1 to 3, got {}",
+                args.len()
+            ))
+        }
+    };
+    if step == 0 {
+        return Object::Error("range step must not be zero".to_string());
+    }
+    if (step > 0 && end < start) || (step < 0 && end > start) {
+        return Object::Error(format!("range step {} never reaches {} from {}", step, end, start));
+    }
+    let (distance, stride) = ((end as i128 - start as i128).abs(), (step as i128).abs());
+    let len = (distance + stride - 1) / stride;
+    if len > MAX_RANGE_LEN {
+        return Object::Error(format!(
+            "range of {} elements exceeds the maximum of {}",
+            len, MAX_RANGE_LEN
+        ));
+    }
+    let mut elements = Vec::with_capacity(len as usize);
+    let mut current = Some(start);
+    while let Some(value) = current.filter(|&value| if step > 0 { value < end } else { value > end }) {
+        elements.push(Object::Integer(value));
+        current = value.checked_add(step);
+    }
+    Object::Array(elements)
+}
+
+/// Returns a new array holding the function applied to each element
+fn map(evaluator: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return wrong_arguments(2, args.len());
+    }
+    let elements = match &args[0] {
+        Object::Array(elements) => elements,
+        other => return Object::Error(format!("argument to `map` must be ARRAY, got {}", other.type_name())),
+    };
+    if let Some(error) = expect_callable("map", &args[1]) {
+        return error;
+    }
+    let mut mapped = Vec::with_capacity(elements.len());
+    for element in elements {
+        let value = evaluator.apply_function(args[1].clone(), vec![element.clone()]);
+        if value.is_error() {
+            return value;
+        }
+        mapped.push(value);
+    }
+    Object::Array(mapped)
+}
+
+/// Returns a new array holding the elements the function returns a truthy value for
+fn filter(evaluator: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return wrong_arguments(2, args.len());
+    }
+    let elements = match &args[0] {
+        Object::Array(elements) => elements,
+        other => return Object::Error(format!("argument to `filter` must be ARRAY, got {}", other.type_name())),
+    };
+    if let Some(error) = expect_callable("filter", &args[1]) {
+        return error;
+    }
+    let mut kept = Vec::new();
+    for element in elements {
+        let keep = evaluator.apply_function(args[1].clone(), vec![element.clone()]);
+        if keep.is_error() {
+            return keep;
+        }
+        if keep.is_truthy() {
+            kept.push(element.clone());
+        }
+    }
+    Object::Array(kept)
+}
+
+/// Folds the array from the left, calling the function with the accumulator
+/// and each element in turn, starting from `initial`
+fn reduce(evaluator: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 3 {
+        return wrong_arguments(3, args.len());
+    }
+    let elements = match &args[0] {
+        Object::Array(elements) => elements,
+        other => return Object::Error(format!("argument to `reduce` must be ARRAY, got {}", other.type_name())),
+    };
+    if let Some(error) = expect_callable("reduce", &args[2]) {
+        return error;
+    }
+    let mut accumulator = args[1].clone();
+    for element in elements {
+        accumulator = evaluator.apply_function(args[2].clone(), vec![accumulator, element.clone()]);
+        if accumulator.is_error() {
+            return accumulator;
+        }
+    }
+    accumulator
+}
+
+/// Splits a string at each occurrence of the separator, or into characters
+/// when the separator is empty
+fn split(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return wrong_arguments(2, args.len());
+    }
+    let parts: Vec<String> = match (&args[0], &args[1]) {
+        (Object::String(value), Object::String(separator)) if separator.is_empty() => {
+            value.chars().map(String::from).collect()
+        }
+        (Object::String(value), Object::String(separator)) => value.split(separator.as_str()).map(String::from).collect(),
+        (Object::String(_), other) | (other, _) => {
+            return Object::Error(format!("argument to `split` must be STRING, got {}", other.type_name()))
+        }
+    };
+    Object::Array(parts.into_iter().map(Object::String).collect())
+}
+
+/// Joins an array of strings into one string, with the separator between each
+fn join(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return wrong_arguments(2, args.len());
+    }
+    let (elements, separator) = match (&args[0], &args[1]) {
+        (Object::Array(elements), Object::String(separator)) => (elements, separator),
+        (Object::Array(_), other) => {
+            return Object::Error(format!("argument to `join` must be STRING, got {}", other.type_name()))
+        }
+        (other, _) => return Object::Error(format!("argument to `join` must be ARRAY, got {}", other.type_name())),
+    };
+    let mut parts = Vec::with_capacity(elements.len());
+    for element in elements {
+        match element {
+            Object::String(value) => parts.push(value.as_str()),
+            other => {
+                return Object::Error(format!("elements joined by `join` must be STRING, got {}", other.type_name()))
+            }
+        }
+    }
+    Object::String(parts.join(separator))
+}
+
+/// Returns the keys of a hash as an array, in the order they were inserted
+fn keys(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::Hash(pairs) => Object::Array(pairs.iter().map(|(key, _)| key.clone()).collect()),
+        other => Object::Error(format!("argument to `keys` must be HASH, got {}", other.type_name())),
+    }
+}
+
+/// Returns the values of a hash as an array, in the same order as `keys`
+fn values(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return wrong_arguments(1, args.len());
+    }
+    match &args[0] {
+        Object::Hash(pairs) => Object::Array(pairs.iter().map(|(_, value)| value.clone()).collect()),
+        other => Object::Error(format!("argument to `values` must be HASH, got {}", other.type_name())),
+    }
+}
+
+/// Whether an array has an element equal to the value, a string contains the
+/// substring, or a hash has the key
+fn contains(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return wrong_arguments(2, args.len());
+    }
+    match (&args[0], &args[1]) {
+        (Object::Array(elements), value) => Object::Boolean(elements.contains(value)),
+        (Object::String(value), Object::String(substring)) => Object::Boolean(value.contains(substring.as_str())),
+        (Object::String(_), other) => Object::Error(format!(
+            "substring passed to `contains` must be STRING, got {}",
+            other.type_name()
+        )),
+        (Object::Hash(pairs), key) => match HashKey::try_from(key.clone()) {
+            Ok(key) => Object::Boolean(pairs.contains_key(&key)),
+            Err(message) => Object::Error(message),
+        },
+        (other, _) => Object::Error(format!("argument to `contains` not supported, got {}", other.type_name())),
+    }
+}
+
+/// Returns a copy of its argument. Arrays and hashes are values, so this is
+/// what binding one to a new name already does, but it makes the copy explicit
+/// before mutating with index assignment. Functions still share the scope they
+/// close over.
+fn clone(_: &mut Evaluator, args: Vec<Object>) -> Object {
+    match <[Object; 1]>::try_from(args) {
+        Ok([value]) => value,
+        Err(args) => wrong_arguments(1, args.len()),
+    }
+}
@@ -0,0 +1,183 @@
+use crate::ast::{Expression, Program, Statement};
+use crate::object::Object;
+use crate::token::Token;
+
+/// A single VM operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// Push the constant at this index in the constant pool
+    Constant(usize),
+    /// Discard the top of the stack
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    True,
+    False,
+    Equal,
+    NotEqual,
+    /// There is no less-than: `a < b` compiles to `b > a` with the operands swapped
+    GreaterThan,
+    GreaterEqual,
+    Minus,
+    Bang,
+}
+
+/// Compiled instructions together with the constants they refer to
+#[derive(Debug, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Vec<Instruction>,
+    pub constants: Vec<Object>,
+}
+
+/// Walks a `Program` and emits instructions for the VM
+#[derive(Default)]
+pub struct Compiler {
+    instructions: Vec<Instruction>,
+    constants: Vec<Object>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), String> {
+        for statement in &program.statements {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    pub fn bytecode(self) -> Bytecode {
+        Bytecode {
+            instructions: self.instructions,
+            constants: self.constants,
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        match statement {
+            Statement::Expression { value, .. } => {
+                self.compile_expression(value)?;
+                self.emit(Instruction::Pop);
+                Ok(())
+            }
+            _ => Err(format!("statement not supported by the compiler: {}", statement)),
+        }
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::IntegerLiteral(value) => {
+                let index = self.add_constant(Object::Integer(*value));
+                self.emit(Instruction::Constant(index));
+            }
+            Expression::Boolean(true) => self.emit(Instruction::True),
+            Expression::Boolean(false) => self.emit(Instruction::False),
+            Expression::Prefix { operator, right } => {
+                self.compile_expression(right)?;
+                match operator {
+                    Token::Minus => self.emit(Instruction::Minus),
+                    Token::Bang => self.emit(Instruction::Bang),
+                    _ => return Err(format!("unknown operator: {}", operator)),
+                }
+            }
+            Expression::Infix { left, operator: Token::Lt, right } => {
+                self.compile_expression(right)?;
+                self.compile_expression(left)?;
+                self.emit(Instruction::GreaterThan);
+            }
+            Expression::Infix { left, operator: Token::Le, right } => {
+                self.compile_expression(right)?;
+                self.compile_expression(left)?;
+                self.emit(Instruction::GreaterEqual);
+            }
+            Expression::Infix { left, operator, right } => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                let instruction = match operator {
+                    Token::Plus => Instruction::Add,
+                    Token::Minus => Instruction::Sub,
+                    Token::Asterisk => Instruction::Mul,
+                    Token::Slash => Instruction::Div,
+                    Token::Percent => Instruction::Mod,
+                    Token::Eq => Instruction::Equal,
+                    Token::NotEq => Instruction::NotEqual,
+                    Token::Gt => Instruction::GreaterThan,
+                    Token::Ge => Instruction::GreaterEqual,
+                    _ => return Err(format!("unknown operator: {}", operator)),
+                };
+                self.emit(instruction);
+            }
+            _ => return Err(format!("expression not supported by the compiler: {}", expression)),
+        }
+        Ok(())
+    }
+
+    fn add_constant(&mut self, object: Object) -> usize {
+        self.constants.push(object);
+        self.constants.len() - 1
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn compile(input: &str) -> Result<Bytecode, String> {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+        Ok(compiler.bytecode())
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        use Instruction::*;
+        let test_cases = vec![
+            ("1 + 2", vec![1, 2], vec![Constant(0), Constant(1), Add, Pop]),
+            ("1; 2", vec![1, 2], vec![Constant(0), Pop, Constant(1), Pop]),
+            ("2 * 3 - 1", vec![2, 3, 1], vec![Constant(0), Constant(1), Mul, Constant(2), Sub, Pop]),
+            ("6 / 2 % 4", vec![6, 2, 4], vec![Constant(0), Constant(1), Div, Constant(2), Mod, Pop]),
+            ("-1", vec![1], vec![Constant(0), Minus, Pop]),
+        ];
+        for (input, constants, instructions) in test_cases {
+            let bytecode = compile(input).unwrap();
+            let constants: Vec<Object> = constants.into_iter().map(Object::Integer).collect();
+            assert_eq!(bytecode, Bytecode { instructions, constants }, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        use Instruction::*;
+        let test_cases = vec![
+            ("true", vec![], vec![True, Pop]),
+            ("!false", vec![], vec![False, Bang, Pop]),
+            ("1 > 2", vec![1, 2], vec![Constant(0), Constant(1), GreaterThan, Pop]),
+            ("1 < 2", vec![2, 1], vec![Constant(0), Constant(1), GreaterThan, Pop]),
+            ("1 <= 2", vec![2, 1], vec![Constant(0), Constant(1), GreaterEqual, Pop]),
+            ("true != false", vec![], vec![True, False, NotEqual, Pop]),
+        ];
+        for (input, constants, instructions) in test_cases {
+            let bytecode = compile(input).unwrap();
+            let constants: Vec<Object> = constants.into_iter().map(Object::Integer).collect();
+            assert_eq!(bytecode, Bytecode { instructions, constants }, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(compile("let x = 1;"), Err("statement not supported by the compiler: let x = 1;".to_string()));
+        assert_eq!(compile("\"a\""), Err("expression not supported by the compiler: a".to_string()));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::ast::Span;
+use crate::lexer::Position;
+use crate::parser::ParseError;
+
+/// An error message, pointing at the span of source it refers to when that
+/// is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { message: message.into(), span }
+    }
+
+    /// Renders the message followed, if there is a span, by the source line
+    /// it starts on with `^` under it, in the style of rustc. A span running
+    /// past the end of its line is underlined to the end of the line.
+    ///
+    /// ```text
+    /// error: Expected Assign, got Int(5)
+    ///  --> 1:7
+    ///   |
+    /// 1 | let x 5;
+    ///   |       ^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("error: {}\n", self.message);
+        let (start, end) = match self.span {
+            Some(span) if source.is_char_boundary(span.0) => span,
+            _ => return out,
+        };
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        let before = &source[line_start..start];
+        let position = Position { line: source[..start].matches('\n').count() + 1, column: before.chars().count() + 1 };
+        let gutter = " ".repeat(position.line.to_string().len());
+        // columns count characters, so pad with the line's own characters
+        // (keeping tabs) to line the carets up however the line is indented
+        let padding: String = before.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let width = source.get(start..end.clamp(start, line_end)).map_or(0, |s| s.chars().count()).max(1);
+        out.push_str(&format!("{}--> {}\n", gutter, position));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", position.line, line));
+        out.push_str(&format!("{} | {}{}\n", gutter, padding, "^".repeat(width)));
+        out
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Diagnostic::new(error.message(), Some(error.span()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_render_parse_error() {
+        let source = "let x = 1;\nlet y 10;\n";
+        let errors = Parser::new(Lexer::new(source)).parse_program().unwrap_err();
+
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render(source),
+            "error: Expected Assign, got Int(10)\n \
+             --> 2:7\n  \
+             |\n\
+             2 | let y 10;\n  \
+             |       ^^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_tabs_and_widens_gutter() {
+        let source = format!("{}\tlet = 5;", "\n".repeat(11));
+        let errors = Parser::new(Lexer::new(&source)).parse_program().unwrap_err();
+
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render(&source),
+            "error: Expected identifier, got Assign\n  \
+             --> 12:6\n   \
+             |\n\
+             12 | \tlet = 5;\n   \
+             | \t    ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_underlines_span_to_end_of_line() {
+        let source = "let x = 1;\nif (x) {\n  y\n}";
+        let diagnostic = Diagnostic::new("identifier not found: y", Some((11, source.len())));
+
+        assert_eq!(
+            diagnostic.render(source),
+            "error: identifier not found: y\n \
+             --> 2:1\n  \
+             |\n\
+             2 | if (x) {\n  \
+             | ^^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let diagnostic = Diagnostic::new("division by zero", None);
+        assert_eq!(diagnostic.render("1 / 0"), "error: division by zero\n");
+    }
+}
@@ -0,0 +1,215 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+use crate::object::Object;
+
+/// Variable bindings visible to the evaluator, optionally enclosed by an outer scope
+#[derive(Default)]
+pub struct Environment {
+    /// Shared with any live snapshots, and copied on the first write after one is taken
+    store: Rc<HashMap<String, Object>>,
+    outer: Option<Rc<RefCell<Environment>>>,
+}
+
+/// The bindings of one scope at some point, to roll back to with
+/// [`Environment::restore`]
+#[derive(Clone)]
+pub struct Snapshot {
+    store: Rc<HashMap<String, Object>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            store: Rc::default(),
+            outer: None,
+        }
+    }
+
+    /// Creates an empty scope whose lookups fall through to `outer`
+    pub fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            store: Rc::default(),
+            outer: Some(outer),
+        }
+    }
+
+    /// Records this scope's bindings. Taking a snapshot doesn't copy them;
+    /// the next change to the scope does.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { store: Rc::clone(&self.store) }
+    }
+
+    /// Puts this scope's bindings back as they were when `snapshot` was
+    /// taken, undoing every `set` and `assign` since. Outer scopes are left
+    /// as they are.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.store = snapshot.store;
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref().and_then(|outer| outer.borrow().get(name)),
+        }
+    }
+
+    pub fn set(&mut self, name: String, val: Object) {
+        Rc::make_mut(&mut self.store).insert(name, val);
+    }
+
+    /// Bindings made in this scope (not its outer scopes), sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Object)> {
+        let mut entries: Vec<(&str, &Object)> = self.store.iter().map(|(name, value)| (name.as_str(), value)).collect();
+        entries.sort_by_key(|(name, _)| *name);
+        entries.into_iter()
+    }
+
+    /// Rebinds `name` in the innermost scope that already defines it,
+    /// returning false if no scope does
+    pub fn assign(&mut self, name: &str, val: Object) -> bool {
+        // checked first so that a miss doesn't copy a store shared with a snapshot
+        if self.store.contains_key(name) {
+            if let Some(slot) = Rc::make_mut(&mut self.store).get_mut(name) {
+                *slot = val;
+            }
+            return true;
+        }
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, val),
+            None => false,
+        }
+    }
+}
+
+// A function captures the environment it is bound in, so environments can be
+// self-referential; Debug only lists names to avoid recursing into closures
+impl Debug for Environment {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.store.keys().collect();
+        names.sort();
+        f.debug_struct("Environment")
+            .field("names", &names)
+            .field("outer", &self.outer.is_some())
+            .finish()
+    }
+}
+
+// Environments are compared by identity for the same reason
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::Environment;
+    use crate::object::Object;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut env = Environment::new();
+        env.set("x".to_string(), Object::Integer(5));
+        env.set("y".to_string(), Object::Boolean(true));
+
+        assert_eq!(env.get("x"), Some(Object::Integer(5)));
+        assert_eq!(env.get("y"), Some(Object::Boolean(true)));
+        assert_eq!(env.get("z"), None);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut env = Environment::new();
+        env.set("x".to_string(), Object::Integer(5));
+        env.set("x".to_string(), Object::Integer(10));
+
+        assert_eq!(env.get("x"), Some(Object::Integer(10)));
+    }
+
+    #[test]
+    fn test_enclosed_environment() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().set("x".to_string(), Object::Integer(5));
+
+        let mut inner = Environment::new_enclosed(Rc::clone(&outer));
+        inner.set("y".to_string(), Object::Integer(10));
+        inner.set("x".to_string(), Object::Integer(1));
+
+        assert_eq!(inner.get("y"), Some(Object::Integer(10)));
+        assert_eq!(inner.get("x"), Some(Object::Integer(1)));
+        assert_eq!(outer.borrow().get("x"), Some(Object::Integer(5)));
+        assert_eq!(outer.borrow().get("y"), None);
+    }
+
+    #[test]
+    fn test_enclosed_environment_reads_outer() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        let inner = Environment::new_enclosed(Rc::clone(&outer));
+        outer.borrow_mut().set("late".to_string(), Object::Boolean(true));
+
+        assert_eq!(inner.get("late"), Some(Object::Boolean(true)));
+    }
+
+    #[test]
+    fn test_iter_is_sorted_by_name() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().set("outer".to_string(), Object::Null);
+        let mut env = Environment::new_enclosed(outer);
+        for name in ["zeta", "alpha", "mid"] {
+            env.set(name.to_string(), Object::String(name.to_string()));
+        }
+        env.set("alpha".to_string(), Object::Integer(1));
+
+        let entries: Vec<(&str, &Object)> = env.iter().collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("alpha", &Object::Integer(1)),
+                ("mid", &Object::String("mid".to_string())),
+                ("zeta", &Object::String("zeta".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assign_updates_defining_scope() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().set("x".to_string(), Object::Integer(5));
+        let mut inner = Environment::new_enclosed(Rc::clone(&outer));
+
+        assert!(inner.assign("x", Object::Integer(6)));
+        assert!(!inner.assign("y", Object::Integer(1)));
+        assert_eq!(outer.borrow().get("x"), Some(Object::Integer(6)));
+        assert_eq!(inner.get("y"), None);
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let mut env = Environment::new();
+        env.set("x".to_string(), Object::Integer(1));
+        let snapshot = env.snapshot();
+
+        env.set("y".to_string(), Object::Integer(2));
+        assert!(env.assign("x", Object::Integer(3)));
+        assert_eq!(env.get("y"), Some(Object::Integer(2)));
+        let later = env.snapshot();
+
+        env.restore(snapshot.clone());
+        assert_eq!(env.get("x"), Some(Object::Integer(1)));
+        assert_eq!(env.get("y"), None);
+
+        // a snapshot can be restored more than once, and isn't changed by
+        // what happens after it is restored
+        env.set("z".to_string(), Object::Null);
+        env.restore(snapshot);
+        assert_eq!(env.get("z"), None);
+        env.restore(later);
+        assert_eq!(env.get("x"), Some(Object::Integer(3)));
+        assert_eq!(env.get("y"), Some(Object::Integer(2)));
+    }
+}
@@ -0,0 +1,1961 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use crate::ast::{Expression, Identifier, Parameter, Program, Span, Statement};
+use crate::builtins;
+use crate::builtins::Builtins;
+use crate::environment::Environment;
+use crate::object::{HashKey, HashPairs, Object};
+use crate::token::Token;
+
+/// Evaluates every statement in the program against `env`, writing any
+/// program output (e.g. from `puts`) to standard output
+pub fn eval(program: &Program, env: &Rc<RefCell<Environment>>) -> Object {
+    Evaluator::new(&mut std::io::stdout()).eval(program, env)
+}
+
+/// Limits and options for an `Evaluator`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalConfig {
+    /// How many function calls may be in progress at once before evaluation
+    /// stops with an error, rather than overflowing the native stack. The
+    /// default is low enough for a debug build to reach it on the 2 MB stack
+    /// a spawned thread gets.
+    pub max_depth: usize,
+    /// What integer arithmetic does when the result doesn't fit in an `i64`
+    pub integer_mode: IntegerMode,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self { max_depth: 200, integer_mode: IntegerMode::default() }
+    }
+}
+
+/// How integer operators handle overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerMode {
+    /// Overflow is an error
+    #[default]
+    Checked,
+    /// Results wrap around in two's complement, so `9223372036854775807 + 1`
+    /// is `-9223372036854775808`
+    Wrapping,
+}
+
+/// Walks the AST, sending program output to an injectable writer
+pub struct Evaluator<'a> {
+    out: &'a mut dyn Write,
+    config: EvalConfig,
+    /// Number of function calls currently being evaluated
+    depth: usize,
+    builtins: Builtins,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(out: &'a mut dyn Write) -> Self {
+        Self::with_config(out, EvalConfig::default())
+    }
+
+    pub fn with_config(out: &'a mut dyn Write, config: EvalConfig) -> Self {
+        Self { out, config, depth: 0, builtins: Builtins::new() }
+    }
+
+    /// Replaces the built-ins that identifiers fall back to
+    pub fn with_builtins(mut self, builtins: Builtins) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    /// Writer that program output should be sent to
+    pub fn output(&mut self) -> &mut dyn Write {
+        self.out
+    }
+
+    /// Evaluates every statement in the program, returning the value of the last one
+    pub fn eval(&mut self, program: &Program, env: &Rc<RefCell<Environment>>) -> Object {
+        self.eval_located(program, env).0
+    }
+
+    /// Like [`eval`](Self::eval), but an error comes with the span of the
+    /// top-level statement that was running when it happened, if it was
+    /// parsed with one
+    pub fn eval_located(&mut self, program: &Program, env: &Rc<RefCell<Environment>>) -> (Object, Option<Span>) {
+        let mut result = Object::Null;
+        for (index, statement) in program.statements.iter().enumerate() {
+            result = self.eval_listed_statement(statement, env);
+            match result {
+                Object::ReturnValue(value) => return (*value, None),
+                Object::Error(_) => return (result, program.span(index)),
+                _ => (),
+            }
+        }
+        (result, None)
+    }
+
+    /// Evaluates a block, stopping at the first `return` or error and leaving it
+    /// as-is so that enclosing blocks stop too. The block's value is that of its
+    /// last expression, unless a trailing `;` turns it into a statement.
+    fn eval_block_statement(&mut self, statements: &[Statement], env: &Rc<RefCell<Environment>>) -> Object {
+        let mut result = Object::Null;
+        for statement in statements {
+            result = self.eval_listed_statement(statement, env);
+            if let Object::ReturnValue(_) | Object::Error(_) = result {
+                return result;
+            }
+        }
+        match statements.last() {
+            Some(Statement::Expression { semicolon: true, .. }) => Object::Null,
+            _ => result,
+        }
+    }
+
+    /// Evaluates one of a list of statements. A block there stands on its own,
+    /// so unlike the body of an `if`, `while` or function it gets a scope of
+    /// its own, and its bindings end with it.
+    fn eval_listed_statement(&mut self, statement: &Statement, env: &Rc<RefCell<Environment>>) -> Object {
+        match statement {
+            Statement::Block { statements } => {
+                let scope = Rc::new(RefCell::new(Environment::new_enclosed(Rc::clone(env))));
+                self.eval_block_statement(statements, &scope)
+            }
+            statement => self.eval_statement(statement, env),
+        }
+    }
+
+    /// Like `eval_statement`, for a statement whose value is the value of the
+    /// function it is in. A call there is left for `apply_function` to make,
+    /// so tail recursion runs in a loop rather than growing the stack.
+    fn eval_tail_statement(&mut self, statement: &Statement, env: &Rc<RefCell<Environment>>) -> Tail {
+        match statement {
+            Statement::Expression { value, .. } | Statement::Return { value } => self.eval_tail_expression(value, env),
+            Statement::Block { statements } => match statements.split_last() {
+                Some((last @ Statement::Expression { semicolon: false, .. }, init))
+                | Some((last @ Statement::Return { .. }, init)) => {
+                    for statement in init {
+                        let result = self.eval_listed_statement(statement, env);
+                        if let Object::ReturnValue(_) | Object::Error(_) = result {
+                            return Tail::Value(result);
+                        }
+                    }
+                    self.eval_tail_statement(last, env)
+                }
+                _ => Tail::Value(self.eval_block_statement(statements, env)),
+            },
+            statement => Tail::Value(self.eval_statement(statement, env)),
+        }
+    }
+
+    fn eval_tail_expression(&mut self, expression: &Expression, env: &Rc<RefCell<Environment>>) -> Tail {
+        match expression {
+            Expression::Call { function, .. } if is_call_to(function, "quote") => {
+                Tail::Value(self.eval_expression(expression, env))
+            }
+            Expression::Call { function, arguments } => match self.eval_call_parts(function, arguments, env) {
+                Ok((function, args)) => Tail::Call(function, args),
+                Err(err) => Tail::Value(err),
+            },
+            Expression::If { condition, consequence, alternative } => match self.eval_condition(condition, env) {
+                Ok(true) => self.eval_tail_statement(consequence, env),
+                Ok(false) => match alternative {
+                    Some(alternative) => self.eval_tail_statement(alternative, env),
+                    None => Tail::Value(Object::Null),
+                },
+                Err(err) => Tail::Value(err),
+            },
+            expression => Tail::Value(self.eval_expression(expression, env)),
+        }
+    }
+
+    pub fn eval_statement(&mut self, statement: &Statement, env: &Rc<RefCell<Environment>>) -> Object {
+        match statement {
+            Statement::Expression { value, .. } => self.eval_expression(value, env),
+            Statement::Let { ident, value } => {
+                let value = self.eval_expression(value, env);
+                if value.is_error() {
+                    return value;
+                }
+                env.borrow_mut().set(ident.value.clone(), value);
+                Object::Null
+            }
+            Statement::Return { value } => {
+                let value = self.eval_expression(value, env);
+                if value.is_error() {
+                    return value;
+                }
+                Object::ReturnValue(Box::new(value))
+            }
+            Statement::Block { statements } => self.eval_block_statement(statements, env),
+            Statement::While { condition, body } => loop {
+                let condition = self.eval_expression(condition, env);
+                if condition.is_error() {
+                    return condition;
+                }
+                if !condition.is_truthy() {
+                    return Object::Null;
+                }
+                let result = self.eval_statement(body, env);
+                if let Object::ReturnValue(_) | Object::Error(_) = result {
+                    return result;
+                }
+            },
+        }
+    }
+
+    fn eval_expression(&mut self, expression: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+        match expression {
+            Expression::IntegerLiteral(value) => Object::Integer(*value),
+            Expression::FloatLiteral(value) => Object::Float(*value),
+            Expression::Boolean(value) => Object::Boolean(*value),
+            Expression::Null => Object::Null,
+            Expression::StringLiteral(value) => Object::String(value.clone()),
+            // characters are their code point
+            Expression::CharLiteral(value) => Object::Integer(*value as i64),
+            Expression::Identifier(ident) => self.eval_identifier(ident, env),
+            Expression::Prefix { operator, right } => self.eval_prefix_expression(operator, right, env),
+            Expression::Infix { left, operator, right } => self.eval_infix_expression(left, operator, right, env),
+            Expression::Assign { name, value } => self.eval_assign_expression(name, value, env),
+            Expression::IndexAssign { left, index, operator, value } => {
+                self.eval_index_assign_expression(left, index, operator.as_ref(), value, env)
+            }
+            Expression::If { condition, consequence, alternative } => {
+                self.eval_if_expression(condition, consequence, alternative.as_deref(), env)
+            }
+            Expression::FunctionLiteral { .. } | Expression::MacroLiteral { .. } => eval_literal(expression, env),
+            Expression::Call { function, arguments } if is_call_to(function, "quote") => self.quote(arguments, env),
+            Expression::Call { function, arguments } => self.eval_call_expression(function, arguments, env),
+            Expression::Array(elements) => self.eval_array_literal(elements, env),
+            Expression::Hash(pairs) => self.eval_hash_literal(pairs, env),
+            Expression::Index { left, index } => self.eval_index_expression(left, index, env),
+            Expression::Slice { left, start, end } => {
+                self.eval_slice_expression(left, start.as_deref(), end.as_deref(), env)
+            }
+        }
+    }
+
+    // The arms of `eval_expression` live in their own methods: a debug build
+    // gives one frame room for every arm's locals, and keeping that frame
+    // small lets deeper Monkey recursion fit on the native stack
+
+    fn eval_identifier(&self, ident: &Identifier, env: &Rc<RefCell<Environment>>) -> Object {
+        env.borrow()
+            .get(&ident.value)
+            .or_else(|| self.builtins.lookup(&ident.value))
+            .unwrap_or_else(|| Object::Error(format!("identifier not found: {}", ident.value)))
+    }
+
+    fn eval_prefix_expression(&mut self, operator: &Token, right: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+        let right = self.eval_expression(right, env);
+        if right.is_error() {
+            return right;
+        }
+        eval_prefix(operator, right, self.config.integer_mode)
+    }
+
+    fn eval_array_literal(&mut self, elements: &[Expression], env: &Rc<RefCell<Environment>>) -> Object {
+        match self.eval_expressions(elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(err) => err,
+        }
+    }
+
+    fn eval_infix_expression(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let left = self.eval_expression(left, env);
+        if left.is_error() {
+            return left;
+        }
+        // the right operand of && and || is only evaluated when it decides the result
+        match operator {
+            Token::And if !left.is_truthy() => return Object::Boolean(false),
+            Token::Or if left.is_truthy() => return Object::Boolean(true),
+            Token::And | Token::Or => {
+                let right = self.eval_expression(right, env);
+                if right.is_error() {
+                    return right;
+                }
+                return Object::Boolean(right.is_truthy());
+            }
+            _ => {}
+        }
+        let right = self.eval_expression(right, env);
+        if right.is_error() {
+            return right;
+        }
+        eval_infix(operator, left, right, self.config.integer_mode)
+    }
+
+    fn eval_assign_expression(&mut self, name: &Identifier, value: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+        let value = self.eval_expression(value, env);
+        if value.is_error() {
+            return value;
+        }
+        if env.borrow_mut().assign(&name.value, value.clone()) {
+            value
+        } else {
+            Object::Error(format!("identifier not found: {}", name.value))
+        }
+    }
+
+    /// Collections are values, so `a[i][j] = v` rebuilds `a[i]` with `v` at
+    /// `j`, then `a` with that at `i`, and rebinds `a` to the result. Other
+    /// bindings holding the old collection don't see the change. A compound
+    /// assignment combines `value` with the element already there, evaluating
+    /// the collection and each index once.
+    fn eval_index_assign_expression(
+        &mut self,
+        left: &Expression,
+        index: &Expression,
+        operator: Option<&Token>,
+        value: &Expression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let mut root = left;
+        let mut path = vec![index];
+        while let Expression::Index { left, index } = root {
+            path.push(index);
+            root = left;
+        }
+        let collection = self.eval_expression(root, env);
+        if collection.is_error() {
+            return collection;
+        }
+        let mut indices = Vec::with_capacity(path.len());
+        for index in path.into_iter().rev() {
+            let index = self.eval_expression(index, env);
+            if index.is_error() {
+                return index;
+            }
+            indices.push(index);
+        }
+        let mut value = self.eval_expression(value, env);
+        if value.is_error() {
+            return value;
+        }
+        if let Some(operator) = operator {
+            let mut current = collection.clone();
+            for index in &indices {
+                current = eval_index(current, index.clone());
+                if current.is_error() {
+                    return current;
+                }
+            }
+            value = eval_infix(operator, current, value, self.config.integer_mode);
+            if value.is_error() {
+                return value;
+            }
+        }
+        let updated = eval_index_assign_path(collection, &indices, value.clone());
+        if updated.is_error() {
+            return updated;
+        }
+        // assigning into a temporary, like `f()[0] = 1`, changes nothing that outlives it
+        if let Expression::Identifier(name) = root {
+            env.borrow_mut().assign(&name.value, updated);
+        }
+        value
+    }
+
+    fn eval_if_expression(
+        &mut self,
+        condition: &Expression,
+        consequence: &Statement,
+        alternative: Option<&Statement>,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        match self.eval_condition(condition, env) {
+            Ok(true) => self.eval_statement(consequence, env),
+            Ok(false) => match alternative {
+                Some(alternative) => self.eval_statement(alternative, env),
+                None => Object::Null,
+            },
+            Err(err) => err,
+        }
+    }
+
+    /// Whether the condition of an `if` is truthy, or the error evaluating it gave
+    fn eval_condition(&mut self, condition: &Expression, env: &Rc<RefCell<Environment>>) -> Result<bool, Object> {
+        let condition = self.eval_expression(condition, env);
+        if condition.is_error() {
+            return Err(condition);
+        }
+        Ok(condition.is_truthy())
+    }
+
+    fn eval_call_expression(&mut self, function: &Expression, arguments: &[Expression], env: &Rc<RefCell<Environment>>) -> Object {
+        match self.eval_call_parts(function, arguments, env) {
+            Ok((function, args)) => self.apply_function(function, args),
+            Err(err) => err,
+        }
+    }
+
+    /// Evaluates the function and then the arguments of a call
+    fn eval_call_parts(
+        &mut self,
+        function: &Expression,
+        arguments: &[Expression],
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<(Object, Vec<Object>), Object> {
+        let function = self.eval_expression(function, env);
+        if function.is_error() {
+            return Err(function);
+        }
+        Ok((function, self.eval_expressions(arguments, env)?))
+    }
+
+    fn eval_index_expression(&mut self, left: &Expression, index: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+        let left = self.eval_expression(left, env);
+        if left.is_error() {
+            return left;
+        }
+        let index = self.eval_expression(index, env);
+        if index.is_error() {
+            return index;
+        }
+        eval_index(left, index)
+    }
+
+    fn eval_slice_expression(
+        &mut self,
+        left: &Expression,
+        start: Option<&Expression>,
+        end: Option<&Expression>,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let left = self.eval_expression(left, env);
+        if left.is_error() {
+            return left;
+        }
+        let mut bounds = Vec::new();
+        for bound in [start, end] {
+            let bound = match bound {
+                Some(bound) => match self.eval_expression(bound, env) {
+                    Object::Integer(i) => Some(i),
+                    Object::Error(message) => return Object::Error(message),
+                    other => return Object::Error(format!("slice bound must be INTEGER, got {}", other.type_name())),
+                },
+                None => None,
+            };
+            bounds.push(bound);
+        }
+        eval_slice(left, bounds[0], bounds[1])
+    }
+
+    /// Wraps the argument of `quote` unevaluated, except that each
+    /// `unquote(x)` inside it is replaced by the AST form of the evaluated `x`
+    fn quote(&mut self, arguments: &[Expression], env: &Rc<RefCell<Environment>>) -> Object {
+        let expression = match arguments {
+            [argument] => argument.clone(),
+            _ => return builtins::wrong_arguments(1, arguments.len()),
+        };
+        let expression = expression.modify(&mut |node| match node {
+            Expression::Call { function, arguments } if is_call_to(&function, "unquote") && arguments.len() == 1 => {
+                let value = self.eval_expression(&arguments[0], env);
+                object_to_expression(value).unwrap_or(Expression::Call { function, arguments })
+            }
+            node => node,
+        });
+        Object::Quote(expression)
+    }
+
+    /// Evaluates expressions left to right, stopping at the first error
+    fn eval_expressions(&mut self, expressions: &[Expression], env: &Rc<RefCell<Environment>>) -> Result<Vec<Object>, Object> {
+        let mut results = Vec::with_capacity(expressions.len());
+        for expression in expressions {
+            let result = self.eval_expression(expression, env);
+            if result.is_error() {
+                return Err(result);
+            }
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    fn eval_hash_literal(&mut self, pairs: &[(Expression, Expression)], env: &Rc<RefCell<Environment>>) -> Object {
+        let mut hash = HashPairs::new();
+        for (key, value) in pairs {
+            let key = self.eval_expression(key, env);
+            if key.is_error() {
+                return key;
+            }
+            let hash_key = match HashKey::try_from(key.clone()) {
+                Ok(hash_key) => hash_key,
+                Err(message) => return Object::Error(message),
+            };
+            let value = self.eval_expression(value, env);
+            if value.is_error() {
+                return value;
+            }
+            hash.insert(hash_key, (key, value));
+        }
+        Object::Hash(hash)
+    }
+
+    /// Binds the arguments to the function's parameters in a scope enclosed by
+    /// the function's captured environment, then evaluates its body. Missing
+    /// trailing arguments take their defaults, evaluated in that scope so a
+    /// default can refer to earlier parameters.
+    /// Built-ins are called directly with the arguments.
+    /// A call the body ends with replaces the current one instead of nesting
+    /// inside it, so it doesn't count towards the recursion limit.
+    pub fn apply_function(&mut self, mut function: Object, mut arguments: Vec<Object>) -> Object {
+        loop {
+            (function, arguments) = match self.apply_once(function, arguments) {
+                Tail::Call(function, arguments) => (function, arguments),
+                Tail::Value(Object::ReturnValue(value)) => return *value,
+                Tail::Value(result) => return result,
+            };
+        }
+    }
+
+    fn apply_once(&mut self, function: Object, arguments: Vec<Object>) -> Tail {
+        match function {
+            Object::Function { parameters, body, env } => {
+                let enclosed = match self.bind_arguments(&parameters, env, arguments) {
+                    Ok(enclosed) => enclosed,
+                    Err(err) => return Tail::Value(err),
+                };
+                if self.depth >= self.config.max_depth {
+                    return Tail::Value(Object::Error("maximum recursion depth exceeded".to_string()));
+                }
+                self.depth += 1;
+                let result = self.eval_tail_statement(&body, &enclosed);
+                self.depth -= 1;
+                result
+            }
+            Object::Builtin(builtin) => Tail::Value(builtin.call(self, arguments)),
+            other => Tail::Value(Object::Error(format!("not a function: {}", other.type_name()))),
+        }
+    }
+
+    /// Makes the scope a function body runs in, checking the number of
+    /// arguments and filling in defaults for missing ones
+    fn bind_arguments(
+        &mut self,
+        parameters: &[Parameter],
+        env: Rc<RefCell<Environment>>,
+        arguments: Vec<Object>,
+    ) -> Result<Rc<RefCell<Environment>>, Object> {
+        let required = parameters.iter().filter(|(_, default)| default.is_none()).count();
+        if arguments.len() < required || arguments.len() > parameters.len() {
+            let expected = if required == parameters.len() {
+                required.to_string()
+            } else {
+                format!("{} to {}", required, parameters.len())
+            };
+            return Err(Object::Error(format!(
+                "wrong number of arguments: expected {}, got {}",
+                expected,
+                arguments.len()
+            )));
+        }
+        let enclosed = Rc::new(RefCell::new(Environment::new_enclosed(env)));
+        let mut arguments = arguments.into_iter();
+        for (param, default) in parameters {
+            let value = match (arguments.next(), default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => self.eval_expression(default, &enclosed),
+                (None, None) => unreachable!("arity was checked above"),
+            };
+            if value.is_error() {
+                return Err(value);
+            }
+            enclosed.borrow_mut().set(param.value.clone(), value);
+        }
+        Ok(enclosed)
+    }
+}
+
+/// What evaluating a function body led to: its value, or a call in tail
+/// position that is yet to be made
+enum Tail {
+    Value(Object),
+    Call(Object, Vec<Object>),
+}
+
+/// The function or macro a literal evaluates to, closing over `env`
+fn eval_literal(literal: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+    match literal {
+        Expression::FunctionLiteral { parameters, body } => Object::Function {
+            parameters: parameters.clone(),
+            body: body.clone(),
+            env: Rc::clone(env),
+        },
+        Expression::MacroLiteral { parameters, body } => Object::Macro {
+            parameters: parameters.clone(),
+            body: body.clone(),
+            env: Rc::clone(env),
+        },
+        other => unreachable!("not a function or macro literal: {:?}", other),
+    }
+}
+
+/// Whether `function` is the bare identifier `name`
+fn is_call_to(function: &Expression, name: &str) -> bool {
+    matches!(function, Expression::Identifier(ident) if ident.value == name)
+}
+
+/// The AST node that evaluates to `object`, for the objects that have one
+fn object_to_expression(object: Object) -> Option<Expression> {
+    match object {
+        Object::Integer(value) => Some(Expression::IntegerLiteral(value)),
+        Object::Float(value) => Some(Expression::FloatLiteral(value)),
+        Object::Boolean(value) => Some(Expression::Boolean(value)),
+        Object::Null => Some(Expression::Null),
+        Object::String(value) => Some(Expression::StringLiteral(value)),
+        Object::Quote(expression) => Some(expression),
+        _ => None,
+    }
+}
+
+/// Out-of-bounds array access and missing hash keys yield `null` rather than an error.
+/// Indexing a string yields a one-character string, counting in characters
+/// rather than bytes, so `"héllo"[1]` is `"é"`
+fn eval_index(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::String(value), Object::Integer(i)) => usize::try_from(i)
+            .ok()
+            .and_then(|i| value.chars().nth(i))
+            .map(|c| Object::String(c.to_string()))
+            .unwrap_or(Object::Null),
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(i)
+            .ok()
+            .and_then(|i| elements.get(i).cloned())
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), index) => match HashKey::try_from(index) {
+            Ok(hash_key) => pairs.get(&hash_key).map(|(_, value)| value.clone()).unwrap_or(Object::Null),
+            Err(message) => Object::Error(message),
+        },
+        (left, _) => Object::Error(format!("index operator not supported: {}", left.type_name())),
+    }
+}
+
+/// Returns `collection` with `value` stored at the end of the chain of
+/// `indices`, rebuilding each collection along the way
+fn eval_index_assign_path(collection: Object, indices: &[Object], value: Object) -> Object {
+    let (index, rest) = match indices.split_first() {
+        Some(split) => split,
+        None => return value,
+    };
+    let value = if rest.is_empty() {
+        value
+    } else {
+        let inner = eval_index(collection.clone(), index.clone());
+        if inner.is_error() {
+            return inner;
+        }
+        eval_index_assign_path(inner, rest, value)
+    };
+    if value.is_error() {
+        return value;
+    }
+    eval_index_assign(collection, index.clone(), value)
+}
+
+/// Stores `value` at `index`: arrays can only replace an existing element,
+/// while hashes also gain new keys
+fn eval_index_assign(left: Object, index: Object, value: Object) -> Object {
+    match (left, index) {
+        (Object::Array(mut elements), Object::Integer(i)) => {
+            let len = elements.len();
+            match usize::try_from(i).ok().and_then(|i| elements.get_mut(i)) {
+                Some(element) => *element = value,
+                None => return Object::Error(format!("index out of bounds: {} (length {})", i, len)),
+            }
+            Object::Array(elements)
+        }
+        (Object::Array(_), index) => Object::Error(format!("array index must be INTEGER, got {}", index.type_name())),
+        (Object::Hash(mut pairs), index) => match HashKey::try_from(index.clone()) {
+            Ok(hash_key) => {
+                pairs.insert(hash_key, (index, value));
+                Object::Hash(pairs)
+            }
+            Err(message) => Object::Error(message),
+        },
+        (left, _) => Object::Error(format!("index assignment not supported: {}", left.type_name())),
+    }
+}
+
+/// Slices a string (by characters) or an array. Bounds are clamped to the
+/// length, so out-of-range slices are shorter rather than errors, and a start
+/// past the end yields an empty result.
+fn eval_slice(left: Object, start: Option<i64>, end: Option<i64>) -> Object {
+    let range = |len: usize| {
+        let clamp = |bound: i64| usize::try_from(bound).unwrap_or(0).min(len);
+        let end = end.map_or(len, clamp);
+        let start = start.map_or(0, clamp).min(end);
+        start..end
+    };
+    match left {
+        Object::String(value) => {
+            let chars: Vec<char> = value.chars().collect();
+            Object::String(chars[range(chars.len())].iter().collect())
+        }
+        Object::Array(elements) => Object::Array(elements[range(elements.len())].to_vec()),
+        other => Object::Error(format!("slice operator not supported: {}", other.type_name())),
+    }
+}
+
+/// `!` negates truthiness and works on any value; `-` only applies to numbers
+fn eval_prefix(operator: &Token, right: Object, mode: IntegerMode) -> Object {
+    match (operator, right) {
+        (Token::Bang, right) => Object::Boolean(!right.is_truthy()),
+        (Token::Minus, Object::Integer(value)) => match mode {
+            IntegerMode::Checked => value.checked_neg().map_or_else(integer_overflow, Object::Integer),
+            IntegerMode::Wrapping => Object::Integer(value.wrapping_neg()),
+        },
+        (Token::Minus, Object::Float(value)) => Object::Float(-value),
+        (_, right) => Object::Error(format!("unknown operator: {}{}", operator, right.type_name())),
+    }
+}
+
+/// Applies a binary operator. Each supported pair of operand types has its own
+/// branch; operands of different types are a type mismatch, even for `==`.
+fn eval_infix(operator: &Token, left: Object, right: Object, mode: IntegerMode) -> Object {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => eval_integer_infix(operator, left, right, mode),
+        (Object::Float(left), Object::Float(right)) => eval_float_infix(operator, left, right),
+        // an integer meeting a float is promoted, so the result is a float
+        (Object::Integer(left), Object::Float(right)) => eval_float_infix(operator, left as f64, right),
+        (Object::Float(left), Object::Integer(right)) => eval_float_infix(operator, left, right as f64),
+        (Object::Boolean(l), Object::Boolean(r)) => match operator {
+            Token::Eq => Object::Boolean(l == r),
+            Token::NotEq => Object::Boolean(l != r),
+            _ => Object::Error(format!("unknown operator: BOOLEAN {} BOOLEAN", operator)),
+        },
+        (Object::String(left), Object::String(right)) => eval_string_infix(operator, left, right),
+        (Object::Null, Object::Null) => match operator {
+            Token::Eq => Object::Boolean(true),
+            Token::NotEq => Object::Boolean(false),
+            _ => Object::Error(format!("unknown operator: NULL {} NULL", operator)),
+        },
+        (left, right) if left.type_name() != right.type_name() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        (left, right) => Object::Error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+/// Integer arithmetic overflows according to `mode`, and `/` truncates
+/// towards zero, so `5 / 2` is `2`. Dividing by zero is an error either way.
+fn eval_integer_infix(operator: &Token, left: i64, right: i64, mode: IntegerMode) -> Object {
+    let arithmetic = |checked: fn(i64, i64) -> Option<i64>, wrapping: fn(i64, i64) -> i64| match mode {
+        IntegerMode::Checked => checked(left, right).map_or_else(integer_overflow, Object::Integer),
+        IntegerMode::Wrapping => Object::Integer(wrapping(left, right)),
+    };
+    match operator {
+        Token::Plus => arithmetic(i64::checked_add, i64::wrapping_add),
+        Token::Minus => arithmetic(i64::checked_sub, i64::wrapping_sub),
+        Token::Asterisk => arithmetic(i64::checked_mul, i64::wrapping_mul),
+        Token::Slash if right == 0 => division_by_zero(),
+        Token::Slash => arithmetic(i64::checked_div, i64::wrapping_div),
+        Token::Percent if right == 0 => modulo_by_zero(),
+        Token::Percent => arithmetic(i64::checked_rem, i64::wrapping_rem),
+        Token::Pow if right < 0 => Object::Error(format!("negative exponent: {}", right)),
+        Token::Pow => arithmetic(
+            |left, right| u32::try_from(right).ok().and_then(|right| left.checked_pow(right)),
+            wrapping_pow,
+        ),
+        Token::Lt => Object::Boolean(left < right),
+        Token::Gt => Object::Boolean(left > right),
+        Token::Le => Object::Boolean(left <= right),
+        Token::Ge => Object::Boolean(left >= right),
+        Token::Eq => Object::Boolean(left == right),
+        Token::NotEq => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+/// Float arithmetic is exact division, so `5.0 / 2.0` is `2.5`; dividing by
+/// zero is an error rather than infinity, as it is for integers
+fn eval_float_infix(operator: &Token, left: f64, right: f64) -> Object {
+    match operator {
+        Token::Plus => Object::Float(left + right),
+        Token::Minus => Object::Float(left - right),
+        Token::Asterisk => Object::Float(left * right),
+        Token::Slash if right == 0.0 => division_by_zero(),
+        Token::Slash => Object::Float(left / right),
+        Token::Percent if right == 0.0 => modulo_by_zero(),
+        Token::Percent => Object::Float(left % right),
+        Token::Pow => Object::Float(left.powf(right)),
+        Token::Lt => Object::Boolean(left < right),
+        Token::Gt => Object::Boolean(left > right),
+        Token::Le => Object::Boolean(left <= right),
+        Token::Ge => Object::Boolean(left >= right),
+        Token::Eq => Object::Boolean(left == right),
+        Token::NotEq => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: FLOAT {} FLOAT", operator)),
+    }
+}
+
+/// `left ** right` for a non-negative exponent, wrapping on overflow. Unlike
+/// `i64::wrapping_pow`, the exponent may be larger than a `u32`.
+fn wrapping_pow(mut base: i64, mut exponent: i64) -> i64 {
+    let mut result: i64 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn integer_overflow() -> Object {
+    Object::Error("integer overflow".to_string())
+}
+
+fn division_by_zero() -> Object {
+    Object::Error("division by zero".to_string())
+}
+
+fn modulo_by_zero() -> Object {
+    Object::Error("modulo by zero".to_string())
+}
+
+fn eval_string_infix(operator: &Token, left: String, right: String) -> Object {
+    match operator {
+        Token::Plus => Object::String(left + &right),
+        Token::Eq => Object::Boolean(left == right),
+        Token::NotEq => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: STRING {} STRING", operator)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn test_eval(input: &str) -> Object {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        eval(&program, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let test_cases = vec![
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+            ("10 % 3", 1),
+            ("-7 % 3", -1),
+            ("2 + 9 % 4 * 3", 5),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_power() {
+        let test_cases = vec![
+            ("2 ** 10", Object::Integer(1024)),
+            ("2 ** 0", Object::Integer(1)),
+            ("2 ** 3 ** 2", Object::Integer(512)),
+            ("(2 ** 3) ** 2", Object::Integer(64)),
+            ("-2 ** 2", Object::Integer(-4)),
+            ("(-2) ** 3", Object::Integer(-8)),
+            ("3 * 2 ** 2", Object::Integer(12)),
+            ("2.0 ** 0.5 * 2.0 ** 0.5", Object::Float(2.0000000000000004)),
+            ("2 ** 63", Object::Error("integer overflow".to_string())),
+            ("2 ** 4294967296", Object::Error("integer overflow".to_string())),
+            ("2 ** -1", Object::Error("negative exponent: -1".to_string())),
+            ("true ** 2", Object::Error("type mismatch: BOOLEAN ** INTEGER".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_division() {
+        let test_cases = vec![
+            ("5 / 2", Object::Integer(2)),
+            ("-5 / 2", Object::Integer(-2)),
+            ("6 / 3", Object::Integer(2)),
+            ("5.0 / 2.0", Object::Float(2.5)),
+            ("1.0 / 4.0 * 2.0", Object::Float(0.5)),
+            ("7.5 % 2.0", Object::Float(1.5)),
+            ("-2.5 + 1.0", Object::Float(-1.5)),
+            ("1.5 < 2.5", Object::Boolean(true)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_mixed_integer_and_float() {
+        let test_cases = vec![
+            ("1 + 2.5", Object::Float(3.5)),
+            ("2.5 + 1", Object::Float(3.5)),
+            ("3 - 0.5", Object::Float(2.5)),
+            ("2 * 1.5", Object::Float(3.0)),
+            ("5 / 2.0", Object::Float(2.5)),
+            ("5.0 / 2", Object::Float(2.5)),
+            ("7 % 2.5", Object::Float(2.0)),
+            ("2 ** 0.5 * 2 ** 0.5", Object::Float(2.0000000000000004)),
+            ("2 ** -1.0", Object::Float(0.5)),
+            ("2 < 2.5", Object::Boolean(true)),
+            ("2.5 <= 2", Object::Boolean(false)),
+            ("2 == 2.0", Object::Boolean(true)),
+            ("2 != 2.5", Object::Boolean(true)),
+            // integers on their own stay integers
+            ("5 / 2", Object::Integer(2)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let test_cases = vec![
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 > 1", false),
+            ("1 <= 1", true),
+            ("2 <= 1", false),
+            ("1 >= 1", true),
+            ("1 >= 2", false),
+            ("10 % 3 == 1", true),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 2", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("false == false", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) == true", false),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_minus_operator() {
+        let test_cases = vec![
+            ("-true", Object::Error("unknown operator: -BOOLEAN".to_string())),
+            ("-null", Object::Error("unknown operator: -NULL".to_string())),
+            ("-\"a\"", Object::Error("unknown operator: -STRING".to_string())),
+            ("-[1]", Object::Error("unknown operator: -ARRAY".to_string())),
+            ("--5", Object::Integer(5)),
+            ("-2.5", Object::Float(-2.5)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_bang_operator() {
+        let test_cases = vec![
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+            ("!0", false),
+            ("!null", true),
+            ("!!null", false),
+            ("!\"\"", false),
+            ("![]", false),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_let_statements() {
+        let test_cases = vec![
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+            ("let a = 5; let a = a * 2; a;", 10),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_unknown_identifier() {
+        assert_eq!(test_eval("foobar"), Object::Error("identifier not found: foobar".to_string()));
+    }
+
+    #[test]
+    fn test_eval_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function { parameters, body, .. } => {
+                assert_eq!(parameters.len(), 1);
+                assert_eq!(parameters[0].0.value, "x");
+                assert_eq!(body.to_string(), "{ (x + 2); }");
+            }
+            other => panic!("Expected Function object, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eval_function_application() {
+        let test_cases = vec![
+            ("let identity = fn(x) { x }; identity(5);", 5),
+            ("let double = fn(x) { x * 2 }; double(5);", 10),
+            ("let add = fn(x, y) { x + y }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x }(5)", 5),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_closures() {
+        let input = "let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(3)";
+        assert_eq!(test_eval(input), Object::Integer(5));
+    }
+
+    #[test]
+    fn test_eval_function_sees_own_binding() {
+        let input = "let f = fn(x) { f }; let g = f(1); g(2)";
+        assert!(matches!(test_eval(input), Object::Function { .. }));
+    }
+
+    #[test]
+    fn test_eval_recursive_functions() {
+        let test_cases = vec![
+            ("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10)", 55),
+            ("let sum = fn() { let go = fn(n) { if (n == 0) { 0 } else { n + go(n - 1) } }; go(4) }; sum()", 10),
+            ("let fact = fn(n) { if (n == 0) { return 1; } n * fact(n - 1) }; fact(5)", 120),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_recursion_limit() {
+        let input = "let f = fn(n) { if (n < 0) { 0 } else { 1 + f(n + 1) } }; f(0)";
+        assert_eq!(test_eval(input).to_string(), "ERROR: maximum recursion depth exceeded");
+        let input = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(199)";
+        assert_eq!(test_eval(input), Object::Integer(199));
+
+        let program = Parser::new(Lexer::new("let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(10)")).parse_program().unwrap();
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out = std::io::sink();
+        let config = EvalConfig { max_depth: 10, ..EvalConfig::default() };
+        assert_eq!(
+            Evaluator::with_config(&mut out, config).eval(&program, &env),
+            Object::Error("maximum recursion depth exceeded".to_string())
+        );
+        let config = EvalConfig { max_depth: 11, ..EvalConfig::default() };
+        assert_eq!(Evaluator::with_config(&mut out, config).eval(&program, &env), Object::Integer(10));
+    }
+
+    #[test]
+    fn test_eval_tail_calls() {
+        let test_cases = vec![
+            ("let countdown = fn(n) { if (n == 0) { 0 } else { countdown(n - 1) } }; countdown(100000)", 0),
+            ("let sum = fn(n, acc) { if (n == 0) { return acc; } sum(n - 1, acc + n) }; sum(100000, 0)", 5000050000),
+            (
+                "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; \
+                 let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; \
+                 if (even(100000)) { 1 } else { 0 }",
+                1,
+            ),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+        // a call followed by `;` isn't the function's value, so it isn't a tail call
+        assert_eq!(test_eval("let f = fn() { len(\"ab\"); }; f()"), Object::Null);
+    }
+
+    #[test]
+    fn test_eval_integer_modes() {
+        let eval_with = |input: &str, integer_mode: IntegerMode| {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            let env = Rc::new(RefCell::new(Environment::new()));
+            let config = EvalConfig { integer_mode, ..EvalConfig::default() };
+            Evaluator::with_config(&mut std::io::sink(), config).eval(&program, &env)
+        };
+        let overflow = Object::Error("integer overflow".to_string());
+        let test_cases = vec![
+            ("4611686018427387904 * 2", overflow.clone(), Object::Integer(i64::MIN)),
+            ("3037000500 * 3037000500", overflow.clone(), Object::Integer(-9223372036709301616)),
+            ("9223372036854775807 + 1", overflow.clone(), Object::Integer(i64::MIN)),
+            ("let min = -9223372036854775807 - 1; -min", overflow.clone(), Object::Integer(i64::MIN)),
+            ("let min = -9223372036854775807 - 1; min / -1", overflow.clone(), Object::Integer(i64::MIN)),
+            ("2 ** 64", overflow.clone(), Object::Integer(0)),
+            ("(-1) ** 5000000001", overflow, Object::Integer(-1)),
+            ("6 * 7", Object::Integer(42), Object::Integer(42)),
+            ("1 / 0", division_by_zero(), division_by_zero()),
+        ];
+        for (input, checked, wrapping) in test_cases {
+            assert_eq!(eval_with(input, IntegerMode::Checked), checked, "{}", input);
+            assert_eq!(eval_with(input, IntegerMode::Wrapping), wrapping, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_default_parameters() {
+        let test_cases = vec![
+            ("let add = fn(x, y = 10) { x + y }; add(1)", 11),
+            ("let add = fn(x, y = 10) { x + y }; add(1, 2)", 3),
+            ("let f = fn(x, y = x * 2, z = y + 1) { x + y + z }; f(1)", 6),
+            ("let f = fn(x, y = x * 2, z = y + 1) { x + y + z }; f(1, 5)", 12),
+            ("let n = 100; let f = fn(x = n) { x }; let n = 1; f()", 1),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_wrong_argument_count() {
+        let test_cases = vec![
+            ("fn(x) { x }()", "wrong number of arguments: expected 1, got 0"),
+            ("fn(x) { x }(1, 2)", "wrong number of arguments: expected 1, got 2"),
+            ("5(1)", "not a function: INTEGER"),
+            ("fn(x, y = 1) { x }()", "wrong number of arguments: expected 1 to 2, got 0"),
+            ("fn(x, y = 1) { x }(1, 2, 3)", "wrong number of arguments: expected 1 to 2, got 3"),
+            ("fn(x, y = z) { x }(1)", "identifier not found: z"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_return_statements() {
+        let test_cases = vec![
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("let f = fn() { return 10; 1; }; f();", 10),
+            ("let f = fn() { let g = fn() { return 1; 2; }; return g() + 10; 3; }; f();", 11),
+            ("let f = fn() { return fn() { return 10; }; }; f()() + 1;", 11),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_error_handling() {
+        let test_cases = vec![
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("let f = fn() { true + false; 5 }; f()", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("let f = fn() { return true + false; }; f() + 1", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("foobar", "identifier not found: foobar"),
+            ("let x = foobar; 5", "identifier not found: foobar"),
+            ("let f = fn(x) { x }; f(foobar)", "identifier not found: foobar"),
+            ("-(5 + true) + 1", "type mismatch: INTEGER + BOOLEAN"),
+            ("10 % 0", "modulo by zero"),
+            ("9223372036854775807 + 1", "integer overflow"),
+            ("-9223372036854775807 - 2", "integer overflow"),
+            ("4611686018427387904 * 2", "integer overflow"),
+            ("let min = -9223372036854775807 - 1; -min", "integer overflow"),
+            ("let min = -9223372036854775807 - 1; min % -1", "integer overflow"),
+            ("let min = -9223372036854775807 - 1; min / -1", "integer overflow"),
+            ("5 / 0", "division by zero"),
+            ("5.0 / 0.0", "division by zero"),
+            ("5.0 % 0.0", "modulo by zero"),
+            ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+            ("1 / 0.0", "division by zero"),
+            ("1.5 % 0", "modulo by zero"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_null_literal() {
+        let test_cases = vec![
+            ("null", Object::Null),
+            ("let x = null; x", Object::Null),
+            ("let f = fn() { return null; 5 }; f()", Object::Null),
+            ("let x = 1; x = null; x", Object::Null),
+            ("!null", Object::Boolean(true)),
+            ("if (null) { 1 } else { 2 }", Object::Integer(2)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_equality() {
+        let test_cases = vec![
+            ("1 == 1", Object::Boolean(true)),
+            ("1 != 2", Object::Boolean(true)),
+            ("true == true", Object::Boolean(true)),
+            ("true != false", Object::Boolean(true)),
+            ("\"a\" == \"a\"", Object::Boolean(true)),
+            ("\"a\" == \"b\"", Object::Boolean(false)),
+            ("\"a\" != \"b\"", Object::Boolean(true)),
+            ("let f = fn() {}; f() == f()", Object::Boolean(true)),
+            ("true == 1", Object::Error("type mismatch: BOOLEAN == INTEGER".to_string())),
+            ("1 != false", Object::Error("type mismatch: INTEGER != BOOLEAN".to_string())),
+            ("\"1\" == 1", Object::Error("type mismatch: STRING == INTEGER".to_string())),
+            ("[1] == [1]", Object::Error("unknown operator: ARRAY == ARRAY".to_string())),
+            ("\"a\" < \"b\"", Object::Error("unknown operator: STRING < STRING".to_string())),
+            ("true > false", Object::Error("unknown operator: BOOLEAN > BOOLEAN".to_string())),
+            ("let x = null; x == null", Object::Boolean(true)),
+            ("null != null", Object::Boolean(false)),
+            ("1 == null", Object::Error("type mismatch: INTEGER == NULL".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_logical_operators() {
+        let test_cases = vec![
+            ("true && true", true),
+            ("true && false", false),
+            ("false || true", true),
+            ("false || false", false),
+            ("1 < 2 && 2 < 3", true),
+            ("false && undefined", false),
+            ("true || undefined", true),
+            ("false && undefined()", false),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+        assert_eq!(test_eval("true && undefined"), Object::Error("identifier not found: undefined".to_string()));
+        assert_eq!(test_eval("false || undefined"), Object::Error("identifier not found: undefined".to_string()));
+    }
+
+    #[test]
+    fn test_eval_while_statements() {
+        assert_eq!(test_eval("while (false) { 5 }"), Object::Null);
+        assert_eq!(test_eval("let f = fn() { while (true) { return 5; } }; f()"), Object::Integer(5));
+        assert_eq!(
+            test_eval("while (true) { 1 + true }"),
+            Object::Error("type mismatch: INTEGER + BOOLEAN".to_string())
+        );
+        assert_eq!(
+            test_eval("while (x) { 1 }"),
+            Object::Error("identifier not found: x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_assignment() {
+        let test_cases = vec![
+            ("let x = 5; x = 10; x", 10),
+            ("let x = 5; x = x + 1", 6),
+            ("let x = 1; let y = 2; x = y = 3; x + y", 6),
+            ("let n = 0; let inc = fn() { n = n + 1 }; inc(); inc(); n", 2),
+            ("let i = 0; let sum = 0; while (i < 5) { i = i + 1; sum = sum + i; } sum", 15),
+            ("let x = 1; x += 4; x", 5),
+            ("let x = 10; x -= 4; x *= 2; x /= 3; x", 4),
+            ("let i = 0; let sum = 0; while (i < 5) { i += 1; sum += i; } sum", 15),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+        assert_eq!(test_eval("x = 5"), Object::Error("identifier not found: x".to_string()));
+        assert_eq!(test_eval("x += 5"), Object::Error("identifier not found: x".to_string()));
+    }
+
+    #[test]
+    fn test_eval_index_assignment() {
+        let test_cases = vec![
+            ("let a = [1, 2, 3]; a[0] = 99; a", "[99, 2, 3]"),
+            ("let a = [1, 2, 3]; a[1] = a[1] * 10", "20"),
+            ("let a = [1, 2, 3]; a[2] += 1; a", "[1, 2, 4]"),
+            ("let m = [[1, 2], [3, 4]]; m[1][1] *= 10", "40"),
+            (r#"let h = {"s": "a"}; h["s"] += "b"; h"#, "{s: ab}"),
+            // the target is evaluated once, so an index with side effects runs once
+            (
+                "let n = 0; let a = [0, 0, 0]; let f = fn() { n = n + 1; n }; a[f()] += 10; [a, n]",
+                "[[0, 10, 0], 1]",
+            ),
+            ("let a = [1]; a[0] += true", "ERROR: type mismatch: INTEGER + BOOLEAN"),
+            ("let h = {\"a\": 1}; h[\"a\"] = 2; h", "{a: 2}"),
+            ("let h = {\"a\": 1}; h[\"b\"] = true; h", "{a: 1, b: true}"),
+            ("let m = [[1, 2], [3, 4]]; m[1][0] = 0; m", "[[1, 2], [0, 4]]"),
+            ("let h = {1: [0]}; h[1][0] = 5; h[1]", "[5]"),
+            // collections are values, so other bindings keep the old one
+            ("let a = [1]; let b = a; a[0] = 2; [a, b]", "[[2], [1]]"),
+            ("let a = [0, 0]; let set = fn(i) { a[i] = i + 1 }; set(0); set(1); a", "[1, 2]"),
+            ("let a = [1, 2, 3]; a[3] = 4", "ERROR: index out of bounds: 3 (length 3)"),
+            ("let a = [1]; a[-1] = 4", "ERROR: index out of bounds: -1 (length 1)"),
+            ("let a = [1]; a[\"x\"] = 4", "ERROR: array index must be INTEGER, got STRING"),
+            ("let h = {}; h[fn(x) { x }] = 1", "ERROR: unusable as hash key: FUNCTION"),
+            ("let s = \"abc\"; s[0] = \"x\"", "ERROR: index assignment not supported: STRING"),
+            ("b[0] = 1", "ERROR: identifier not found: b"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_if_else_expressions() {
+        let test_cases = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (0) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+            ("if (if (false) { 1 }) { 10 } else { 20 }", Object::Integer(20)),
+            ("let x = 5; if (x < 3) { 1 } else if (x < 10) { 2 } else { 3 }", Object::Integer(2)),
+            ("if (false) { 1 } else if (false) { 2 }", Object::Null),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_block_value() {
+        let test_cases = vec![
+            ("if (true) { 5 }", Object::Integer(5)),
+            ("if (true) { 5; }", Object::Null),
+            ("if (true) { 1; 2 }", Object::Integer(2)),
+            ("if (true) { 1; 2; }", Object::Null),
+            ("fn() { 5 }()", Object::Integer(5)),
+            ("fn() { 5; }()", Object::Null),
+            ("fn() { return 5; }()", Object::Integer(5)),
+            ("fn() { let x = 5; x }()", Object::Integer(5)),
+            ("fn() { let x = 5; }()", Object::Null),
+            // at the top level the last value is kept, so the REPL echoes `5;`
+            ("5;", Object::Integer(5)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_block_statement() {
+        let test_cases = vec![
+            ("{ let x = 5; x + 1 }", "6"),
+            ("{ 1; }", "null"),
+            ("let x = 1; { let x = 2; x }", "2"),
+            // bindings made in the block don't leak out, but assignments reach outer ones
+            ("let x = 1; { let x = 2; } x", "1"),
+            ("let x = 1; { x = 2; } x", "2"),
+            ("{ let y = 1; } y", "ERROR: identifier not found: y"),
+            ("let f = fn() { { return 1; } 2 }; f()", "1"),
+            ("let f = fn(n) { { let m = n * 2; m } }; f(4)", "8"),
+            ("{ let y = 1; { let z = y + 1; z * 10 } }", "20"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_displayed_program_evaluates_the_same() {
+        let inputs = [
+            "{ 5; }",
+            "{ 5 }",
+            "let f = fn() { 1; }; f()",
+            "let f = fn() { 1 }; f()",
+            "if (true) { 1; } else { 2 }",
+            "let a = [1, 2]; let g = fn() { a[0] += 1; }; [g(), a]",
+        ];
+        for input in inputs {
+            let printed = Parser::new(Lexer::new(input)).parse_program().unwrap().to_string();
+            assert_eq!(test_eval(&printed), test_eval(input), "{} printed as {}", input, printed);
+        }
+    }
+
+    #[test]
+    fn test_eval_nested_return_in_if() {
+        let test_cases = vec![
+            ("if (true) { if (true) { return 10; } return 1; }", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; } 5", 10),
+            ("let f = fn(x) { if (x > 1) { return x; } 0 }; f(3) + f(1)", 3),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_quote() {
+        let test_cases = vec![
+            ("quote(5)", "5"),
+            ("quote(5 + 8)", "(5 + 8)"),
+            ("quote(foobar)", "foobar"),
+            ("quote(foobar + barfoo)", "(foobar + barfoo)"),
+        ];
+        for (input, expected) in test_cases {
+            match test_eval(input) {
+                Object::Quote(expression) => assert_eq!(expression.to_string(), expected, "{}", input),
+                other => panic!("Expected Quote, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_quote_unquote() {
+        let test_cases = vec![
+            ("quote(unquote(4))", "4"),
+            ("quote(unquote(4 + 4))", "8"),
+            ("quote(8 + unquote(4 + 4))", "(8 + 8)"),
+            ("quote(unquote(4 + 4) + 8)", "(8 + 8)"),
+            ("let foobar = 8; quote(foobar)", "foobar"),
+            ("let foobar = 8; quote(unquote(foobar))", "8"),
+            ("quote(unquote(true))", "true"),
+            ("quote(unquote(true == false))", "false"),
+            ("quote(unquote(quote(4 + 4)))", "(4 + 4)"),
+            (
+                "let quotedInfixExpression = quote(4 + 4); quote(unquote(4 + 4) + unquote(quotedInfixExpression))",
+                "(8 + (4 + 4))",
+            ),
+        ];
+        for (input, expected) in test_cases {
+            match test_eval(input) {
+                Object::Quote(expression) => assert_eq!(expression.to_string(), expected, "{}", input),
+                other => panic!("Expected Quote, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_string_literal() {
+        assert_eq!(test_eval(r#""Hello World!""#), Object::String("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn test_eval_char_literal() {
+        assert_eq!(test_eval("'a'"), Object::Integer(97));
+        assert_eq!(test_eval("'\\n'"), Object::Integer(10));
+        assert_eq!(test_eval("'b' - 'a'"), Object::Integer(1));
+    }
+
+    #[test]
+    fn test_eval_string_concatenation() {
+        let input = r#""Hello" + " " + "World""#;
+        assert_eq!(test_eval(input), Object::String("Hello World".to_string()));
+    }
+
+    #[test]
+    fn test_eval_string_unknown_operator() {
+        let test_cases = vec![
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""Hello" * "World""#, "unknown operator: STRING * STRING"),
+            (r#""Hello" / "World""#, "unknown operator: STRING / STRING"),
+            (r#""Hello" + 1"#, "type mismatch: STRING + INTEGER"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_array_literal() {
+        let expected = Object::Array(vec![Object::Integer(1), Object::Integer(4), Object::Integer(6)]);
+        assert_eq!(test_eval("[1, 2 * 2, 3 + 3]"), expected);
+    }
+
+    #[test]
+    fn test_eval_array_index_expressions() {
+        let test_cases = vec![
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1]", Object::Integer(2)),
+            ("[1, 2, 3][2]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", Object::Integer(6)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+            ("[][0]", Object::Null),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_string_index_and_slice() {
+        let string = |value: &str| Object::String(value.to_string());
+        let test_cases = vec![
+            (r#""hello"[0]"#, string("h")),
+            (r#""hello"[4]"#, string("o")),
+            (r#""héllo"[1]"#, string("é")),
+            (r#""hello"[5]"#, Object::Null),
+            (r#""hello"[-1]"#, Object::Null),
+            (r#""hello"[1:3]"#, string("el")),
+            (r#""hello"[:2]"#, string("he")),
+            (r#""hello"[3:]"#, string("lo")),
+            (r#""hello"[:]"#, string("hello")),
+            (r#""hello"[2:100]"#, string("llo")),
+            (r#""hello"[4:2]"#, string("")),
+            (r#""héllo"[1:3]"#, string("él")),
+            ("[1, 2, 3, 4][1:3]", Object::Array(vec![Object::Integer(2), Object::Integer(3)])),
+            (r#""hello"["a":]"#, Object::Error("slice bound must be INTEGER, got STRING".to_string())),
+            ("5[1:2]", Object::Error("slice operator not supported: INTEGER".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_index_not_supported() {
+        assert_eq!(test_eval("1[0]"), Object::Error("index operator not supported: INTEGER".to_string()));
+    }
+
+    #[test]
+    fn test_eval_hash_literal() {
+        let input = r#"let two = "two";
+        {
+            "one": 10 - 9,
+            two: 1 + 1,
+            "thr" + "ee": 6 / 2,
+            4: 4,
+            true: 5,
+            false: 6
+        }"#;
+        let pairs = match test_eval(input) {
+            Object::Hash(pairs) => pairs,
+            other => panic!("Expected Hash object, got {:?}", other),
+        };
+        let expected = vec![
+            (Object::String("one".to_string()), 1),
+            (Object::String("two".to_string()), 2),
+            (Object::String("three".to_string()), 3),
+            (Object::Integer(4), 4),
+            (Object::Boolean(true), 5),
+            (Object::Boolean(false), 6),
+        ];
+        assert_eq!(pairs.len(), expected.len());
+        for (key, value) in expected {
+            let (stored_key, stored_value) = &pairs[&HashKey::try_from(key.clone()).unwrap()];
+            assert_eq!(stored_key, &key);
+            assert_eq!(stored_value, &Object::Integer(value));
+        }
+    }
+
+    #[test]
+    fn test_eval_hash_keys_collide() {
+        let test_cases = vec![
+            (r#"let h = {"a": 1, "a": 2}; len(keys(h))"#, Object::Integer(1)),
+            (r#"{"a": 1, "a": 2}["a"]"#, Object::Integer(2)),
+            (r#"let k = "a"; ({"a" + "b": 1})[k + "b"]"#, Object::Integer(1)),
+            (r#"{1: "int", "1": "string", true: "bool"}[1]"#, Object::String("int".to_string())),
+            (r#"{1: "int", "1": "string", true: "bool"}["1"]"#, Object::String("string".to_string())),
+            (r#"{1: "int", "1": "string", true: "bool"}[true]"#, Object::String("bool".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_hash_index_expressions() {
+        let test_cases = vec![
+            (r#"{"a": 5}["a"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_unusable_hash_key() {
+        let test_cases = vec![
+            (r#"{"name": "Monkey"}[fn(x) { x }];"#, "unusable as hash key: FUNCTION"),
+            ("({fn(x) { x }: 1})", "unusable as hash key: FUNCTION"),
+            ("({[1]: 1})", "unusable as hash key: ARRAY"),
+            ("let f = fn() { 1 }; {1: f}[f]", "unusable as hash key: FUNCTION"),
+            ("{1.5: 1}", "unusable as hash key: FLOAT"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_len() {
+        let test_cases = vec![
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("hello world")"#, Object::Integer(11)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("len([])", Object::Integer(0)),
+            ("len(1)", Object::Error("argument to `len` not supported, got INTEGER".to_string())),
+            (r#"len("one", "two")"#, Object::Error("wrong number of arguments: expected 1, got 2".to_string())),
+            ("len()", Object::Error("wrong number of arguments: expected 1, got 0".to_string())),
+            ("let len = fn(x) { 42 }; len([1])", Object::Integer(42)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_array_functions() {
+        let array = |values: Vec<i64>| Object::Array(values.into_iter().map(Object::Integer).collect());
+        let test_cases = vec![
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            ("rest([1, 2, 3])", array(vec![2, 3])),
+            ("rest([1])", array(vec![])),
+            ("rest([])", Object::Null),
+            ("push([1, 2], 3)", array(vec![1, 2, 3])),
+            ("push([], 1)", array(vec![1])),
+            ("let a = [1, 2]; let b = push(a, 3); a", array(vec![1, 2])),
+            ("let a = [1, 2, 3]; rest(a); a", array(vec![1, 2, 3])),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_array_function_errors() {
+        let test_cases = vec![
+            ("first(1)", "argument to `first` must be ARRAY, got INTEGER"),
+            (r#"last("abc")"#, "argument to `last` must be ARRAY, got STRING"),
+            ("rest(true)", "argument to `rest` must be ARRAY, got BOOLEAN"),
+            ("push(1, 1)", "argument to `push` must be ARRAY, got INTEGER"),
+            ("push([1])", "wrong number of arguments: expected 2, got 1"),
+            ("first([1], [2])", "wrong number of arguments: expected 1, got 2"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_type() {
+        let test_cases = vec![
+            ("type(1)", "INTEGER"),
+            ("type(1.5)", "FLOAT"),
+            ("type(true)", "BOOLEAN"),
+            (r#"type("a")"#, "STRING"),
+            ("type([1])", "ARRAY"),
+            ("type({1: 2})", "HASH"),
+            ("type(fn(x) { x })", "FUNCTION"),
+            ("type(len)", "BUILTIN"),
+            ("type(if (false) { 1 })", "NULL"),
+            ("type(type(1))", "STRING"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::String(expected.to_string()), "{}", input);
+        }
+        assert_eq!(test_eval("type()"), Object::Error("wrong number of arguments: expected 1, got 0".to_string()));
+        assert_eq!(test_eval("type(1, 2)"), Object::Error("wrong number of arguments: expected 1, got 2".to_string()));
+    }
+
+    #[test]
+    fn test_builtin_str() {
+        let test_cases = vec![
+            ("str(5)", "5"),
+            ("str(-12)", "-12"),
+            ("str(true)", "true"),
+            ("str([1, 2])", "[1, 2]"),
+            (r#"str("a")"#, "a"),
+            (r#""n = " + str(1 + 2)"#, "n = 3"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::String(expected.to_string()), "{}", input);
+        }
+        assert_eq!(test_eval("str()"), Object::Error("wrong number of arguments: expected 1, got 0".to_string()));
+        assert_eq!(test_eval("str(1, 2)"), Object::Error("wrong number of arguments: expected 1, got 2".to_string()));
+    }
+
+    #[test]
+    fn test_builtin_int() {
+        let test_cases = vec![
+            (r#"int("42")"#, 42),
+            (r#"int("-7")"#, -7),
+            (r#"int(" 3 ")"#, 3),
+            ("int(true)", 1),
+            ("int(false)", 0),
+            ("int(5)", 5),
+            ("int(str(12)) + 1", 13),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_int_errors() {
+        let test_cases = vec![
+            (r#"int("nope")"#, "could not parse 'nope' as INTEGER"),
+            (r#"int("1.5")"#, "could not parse '1.5' as INTEGER"),
+            ("int([1])", "argument to `int` not supported, got ARRAY"),
+            ("int(fn(x) { x })", "argument to `int` not supported, got FUNCTION"),
+            ("int()", "wrong number of arguments: expected 1, got 0"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_range() {
+        let array = |values: Vec<i64>| Object::Array(values.into_iter().map(Object::Integer).collect());
+        let test_cases = vec![
+            ("range(5)", array(vec![0, 1, 2, 3, 4])),
+            ("range(0)", array(vec![])),
+            ("range(0, 5)", array(vec![0, 1, 2, 3, 4])),
+            ("range(3, 6)", array(vec![3, 4, 5])),
+            ("range(2, 2)", array(vec![])),
+            ("range(0, 10, 2)", array(vec![0, 2, 4, 6, 8])),
+            ("range(0, 9, 3)", array(vec![0, 3, 6])),
+            ("range(5, 0, -2)", array(vec![5, 3, 1])),
+            ("len(range(100))", Object::Integer(100)),
+            ("len(range(0, 2000000, 2))", Object::Integer(1000000)),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_range_errors() {
+        let test_cases = vec![
+            ("range(0, 10, 0)", "range step must not be zero"),
+            ("range(0, 10, -1)", "range step -1 never reaches 10 from 0"),
+            ("range(5, 0)", "range step 1 never reaches 0 from 5"),
+            (r#"range("5")"#, "argument to `range` must be INTEGER, got STRING"),
+            ("range()", "wrong number of arguments: expected 1 to 3, got 0"),
+            ("range(1, 2, 3, 4)", "wrong number of arguments: expected 1 to 3, got 4"),
+            ("range(10000000000)", "range of 10000000000 elements exceeds the maximum of 1000000"),
+            ("range(0, 10000000, 5)", "range of 2000000 elements exceeds the maximum of 1000000"),
+            ("range(-9223372036854775807, 9223372036854775807)", "range of 18446744073709551614 elements exceeds the maximum of 1000000"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_map_and_filter() {
+        let array = |values: Vec<i64>| Object::Array(values.into_iter().map(Object::Integer).collect());
+        let test_cases = vec![
+            ("map([1, 2, 3], fn(x) { x * 2 })", array(vec![2, 4, 6])),
+            ("map([], fn(x) { x * 2 })", array(vec![])),
+            ("let square = fn(x) { x * x }; map(range(4), square)", array(vec![0, 1, 4, 9])),
+            (r#"map(["a", "bc"], len)"#, array(vec![1, 2])),
+            ("filter([1, 2, 3, 4], fn(x) { x % 2 == 0 })", array(vec![2, 4])),
+            ("let big = fn(x) { x > 2 }; filter([1, 5, 2, 3], big)", array(vec![5, 3])),
+            ("filter([1, 0, 2], fn(x) { x })", array(vec![1, 0, 2])),
+            ("let a = [1, 2]; map(a, fn(x) { x + 1 }); a", array(vec![1, 2])),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_map_and_filter_errors() {
+        let test_cases = vec![
+            ("map([1], 1)", "argument to `map` must be FUNCTION, got INTEGER"),
+            ("filter([1], [2])", "argument to `filter` must be FUNCTION, got ARRAY"),
+            ("map(1, fn(x) { x })", "argument to `map` must be ARRAY, got INTEGER"),
+            ("filter([1])", "wrong number of arguments: expected 2, got 1"),
+            ("map([1], fn(x, y) { x })", "wrong number of arguments: expected 2, got 1"),
+            ("map([1, true], fn(x) { -x })", "unknown operator: -BOOLEAN"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_reduce() {
+        let test_cases = vec![
+            ("reduce([1, 2, 3], 0, fn(acc, x) { acc + x })", Object::Integer(6)),
+            ("reduce([], 7, fn(acc, x) { acc + x })", Object::Integer(7)),
+            ("let product = fn(acc, x) { acc * x }; reduce(range(1, 6), 1, product)", Object::Integer(120)),
+            (r#"reduce(["a", "b", "c"], "", fn(acc, x) { acc + x })"#, Object::String("abc".to_string())),
+            ("reduce([1, 2], [], push)", Object::Array(vec![Object::Integer(1), Object::Integer(2)])),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_reduce_errors() {
+        let test_cases = vec![
+            ("reduce([1], 0)", "wrong number of arguments: expected 3, got 2"),
+            ("reduce([1], 0, 1)", "argument to `reduce` must be FUNCTION, got INTEGER"),
+            ("reduce(1, 0, fn(acc, x) { acc })", "argument to `reduce` must be ARRAY, got INTEGER"),
+            ("reduce([1], 0, fn(x) { x })", "wrong number of arguments: expected 1, got 2"),
+            ("reduce([1, true], 0, fn(acc, x) { acc + x })", "type mismatch: INTEGER + BOOLEAN"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_split_and_join() {
+        let strings = |values: Vec<&str>| Object::Array(values.into_iter().map(|v| Object::String(v.to_string())).collect());
+        let test_cases = vec![
+            (r#"split("a,b,c", ",")"#, strings(vec!["a", "b", "c"])),
+            (r#"split("a, b", ", ")"#, strings(vec!["a", "b"])),
+            (r#"split("a,,b,", ",")"#, strings(vec!["a", "", "b", ""])),
+            (r#"split("abc", ";")"#, strings(vec!["abc"])),
+            (r#"split("héllo", "")"#, strings(vec!["h", "é", "l", "l", "o"])),
+            (r#"split("", "")"#, strings(vec![])),
+            (r#"join(["a", "b", "c"], "-")"#, Object::String("a-b-c".to_string())),
+            (r#"join(["a"], ", ")"#, Object::String("a".to_string())),
+            (r#"join([], ", ")"#, Object::String("".to_string())),
+            (r#"join(split("a b c", " "), "")"#, Object::String("abc".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_split_and_join_errors() {
+        let test_cases = vec![
+            (r#"split(1, ",")"#, "argument to `split` must be STRING, got INTEGER"),
+            (r#"split("a,b", [","])"#, "argument to `split` must be STRING, got ARRAY"),
+            (r#"split("a")"#, "wrong number of arguments: expected 2, got 1"),
+            (r#"join("abc", ",")"#, "argument to `join` must be ARRAY, got STRING"),
+            (r#"join(["a"], 1)"#, "argument to `join` must be STRING, got INTEGER"),
+            (r#"join(["a", 1], ",")"#, "elements joined by `join` must be STRING, got INTEGER"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_keys_and_values() {
+        let strings = |values: Vec<&str>| Object::Array(values.into_iter().map(|v| Object::String(v.to_string())).collect());
+        let integers = |values: Vec<i64>| Object::Array(values.into_iter().map(Object::Integer).collect());
+        let test_cases = vec![
+            (r#"keys({"b": 2, "a": 1})"#, strings(vec!["b", "a"])),
+            (r#"values({"b": 2, "a": 1})"#, integers(vec![2, 1])),
+            ("keys({3: 0, 1: 0, 2: 0})", integers(vec![3, 1, 2])),
+            ("keys({})", Object::Array(vec![])),
+            (r#"let h = {"x": 1, "y": 2}; map(keys(h), fn(k) { h[k] * 10 })"#, integers(vec![10, 20])),
+            ("keys([1])", Object::Error("argument to `keys` must be HASH, got ARRAY".to_string())),
+            ("values(1)", Object::Error("argument to `values` must be HASH, got INTEGER".to_string())),
+            ("keys()", Object::Error("wrong number of arguments: expected 1, got 0".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_contains() {
+        let test_cases = vec![
+            ("contains([1, 2, 3], 2)", Object::Boolean(true)),
+            ("contains([1, 2, 3], 4)", Object::Boolean(false)),
+            ("contains([1, \"2\"], 2)", Object::Boolean(false)),
+            ("contains([[1], true], [1])", Object::Boolean(true)),
+            ("contains([], null)", Object::Boolean(false)),
+            (r#"contains("hello", "ell")"#, Object::Boolean(true)),
+            (r#"contains("hello", "")"#, Object::Boolean(true)),
+            (r#"contains("hello", "olleh")"#, Object::Boolean(false)),
+            (r#"contains({"a": 1}, "a")"#, Object::Boolean(true)),
+            (r#"contains({"a": 1}, 1)"#, Object::Boolean(false)),
+            (r#"contains({true: 1}, true)"#, Object::Boolean(true)),
+            (r#"contains("123", 1)"#, Object::Error("substring passed to `contains` must be STRING, got INTEGER".to_string())),
+            ("contains({}, [])", Object::Error("unusable as hash key: ARRAY".to_string())),
+            ("contains(12, 1)", Object::Error("argument to `contains` not supported, got INTEGER".to_string())),
+            ("contains([1])", Object::Error("wrong number of arguments: expected 2, got 1".to_string())),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_clone() {
+        let test_cases = vec![
+            ("let a = [1, [2]]; let b = clone(a); b[1][0] = 3; [a, b]", "[[1, [2]], [1, [3]]]"),
+            ("let a = [1, [2]]; let b = clone(a); a[0] = 0; [a, b]", "[[0, [2]], [1, [2]]]"),
+            (r#"let h = {"k": [1]}; let c = clone(h); c["k"][0] = 2; c["n"] = 1; [h, c]"#, "[{k: [1]}, {k: [2], n: 1}]"),
+            (r#"let h = {"k": 1}; let c = clone(h); h["k"] = 5; c["k"]"#, "1"),
+            ("clone(5)", "5"),
+            (r#"clone("s")"#, "s"),
+            ("clone(null)", "null"),
+            ("let f = fn(x) { x * 2 }; clone(f)(4)", "8"),
+            ("clone()", "ERROR: wrong number of arguments: expected 1, got 0"),
+            ("clone(1, 2)", "ERROR: wrong number of arguments: expected 1, got 2"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_puts() {
+        let input = r#"puts("hello", 42); puts(true, [1, 2]); puts()"#;
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        let result = Evaluator::new(&mut out).eval(&program, &Rc::new(RefCell::new(Environment::new())));
+
+        assert_eq!(result, Object::Null);
+        assert_eq!(String::from_utf8(out).unwrap(), "hello\n42\ntrue\n[1, 2]\n");
+    }
+}
@@ -1,9 +1,36 @@
-use std::iter::Peekable;
-use std::str::{Chars};
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use crate::token::Token;
 
+/// A 1-based line and column in the source text
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Clone)]
 pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>,
+    input: &'a str,
+    /// Byte index of the next unread character in `input`
+    offset: usize,
+    line: usize,
+    column: usize,
+    token_start: Position,
+    /// Byte index of the first character of the most recently lexed token
+    token_start_offset: usize,
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -23,41 +50,155 @@ impl<'a> Iterator for Lexer<'a> {
 impl <'a> Lexer<'a> {
     pub fn new (input: &'a str) -> Self {
         Self {
-            input: input.chars().peekable(),
+            input,
+            offset: 0,
+            line: 1,
+            column: 1,
+            token_start: Position::default(),
+            token_start_offset: 0,
         }
     }
     
+    /// Position of the first character of the most recently lexed token
+    pub fn position(&self) -> Position {
+        self.token_start
+    }
+
+    /// Byte range `(start, end)` of the most recently lexed token
+    pub fn span(&self) -> (usize, usize) {
+        (self.token_start_offset, self.offset)
+    }
+
+    /// Lexes the remaining input into a vector that, unlike iterating, ends with `Token::EOF`
+    pub fn tokenize(mut self) -> Vec<Token> {
+        let mut tokens: Vec<Token> = self.by_ref().collect();
+        tokens.push(Token::EOF);
+        tokens
+    }
+
+    /// Turns the lexer into an iterator of tokens with their byte ranges
+    pub fn spanned(self) -> Tokens<'a> {
+        Tokens { lexer: self }
+    }
+    
+    /// Consumes the next character, advancing the line and column counters
+    pub fn next_char(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+    
     pub fn read_char(&mut self) -> char {
-        self.input.next().unwrap_or('\0')
+        self.next_char().unwrap_or('\0')
     }
     
-    pub fn peek_char(&mut self) -> Option<&char> {
-        self.input.peek()
+    pub fn peek_char(&self) -> Option<char> {
+        match *self.input.as_bytes().get(self.offset)? {
+            byte if byte.is_ascii() => Some(byte as char),
+            _ => self.input[self.offset..].chars().next(),
+        }
     }
    
+    /// Whether `c` can start an identifier: `_` or a letter in any script
     pub fn is_letter(c: char) -> bool {
-        c.is_ascii_alphabetic() || c == '_'
+        c.is_alphabetic() || c == '_'
     } 
+
+    /// Whether `c` can continue an identifier, which also allows digits and
+    /// combining marks, so `x2` and `é` spelled as `e` + U+0301 are one name
+    pub fn is_identifier_continue(c: char) -> bool {
+        Self::is_letter(c) || c.is_numeric() || is_combining_mark(c)
+    }
     
     pub fn is_digit(c: char) -> bool {
         c.is_ascii_digit()
     }
    
+    /// Reads an integer, or a float when the digits are followed by a `.` and at least one more digit.
+    /// A dot that isn't followed by a digit is left in the input, so `5.` lexes as `5` then an illegal `.`.
     pub fn read_number(&mut self, c: char) -> Option<Token> {
+        if c == '0' {
+            match self.peek_char() {
+                Some('x') | Some('X') => return self.read_radix_number(16),
+                Some('b') | Some('B') => return self.read_radix_number(2),
+                _ => (),
+            }
+        }
         let mut num = String::from(c);
-        while let Some(&c) = self.peek_char() {
-            if Self::is_digit(c) {
+        self.read_digits(&mut num);
+        if self.peek_char() == Some('.') && self.peek_second_char().is_some_and(Self::is_digit) {
+            num.push(self.read_char());
+            self.read_digits(&mut num);
+            if !Self::valid_separators(&num) {
+                return None;
+            }
+            return num.replace('_', "").parse().ok().map(Token::Float);
+        }
+        if !Self::valid_separators(&num) {
+            return None;
+        }
+        // only overflow can fail here, since `num` is all digits
+        Some(num.replace('_', "").parse().map(Token::Int).unwrap_or(Token::IntTooLarge(num)))
+    }
+
+    /// Reads the digits of a `0x`/`0b` literal, after the `0`. Any trailing
+    /// letters or digits are taken as part of the literal so that `0b2` or
+    /// `0xfg` is rejected as a whole.
+    fn read_radix_number(&mut self, radix: u32) -> Option<Token> {
+        let prefix = self.read_char();
+        let mut digits = String::new();
+        while let Some(c) = self.peek_char() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                digits.push(self.read_char());
+            } else {
+                break;
+            }
+        }
+        if digits.is_empty() || !Self::valid_separators(&digits) {
+            return None;
+        }
+        let cleaned = digits.replace('_', "");
+        if !cleaned.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        // only overflow can fail here, since every char is a valid digit
+        Some(i64::from_str_radix(&cleaned, radix)
+            .map(Token::Int)
+            .unwrap_or(Token::IntTooLarge(format!("0{}{}", prefix, digits))))
+    }
+
+    /// Underscores may only separate two digits, so `1_000` is fine but
+    /// `_5`, `5_`, `5__0` and `5_.0` are not
+    fn valid_separators(num: &str) -> bool {
+        !num.contains("__") && !num.contains("_.") && !num.starts_with('_') && !num.ends_with('_')
+    }
+
+    /// Reads digits and `_` separators into `num`
+    fn read_digits(&mut self, num: &mut String) {
+        while let Some(c) = self.peek_char() {
+            if Self::is_digit(c) || c == '_' {
                 num.push(self.read_char());
             } else {
                 break;
             }
         }
-        num.parse().ok().map(Token::Int)
+    }
+
+    fn peek_second_char(&self) -> Option<char> {
+        let mut ahead = self.input[self.offset..].chars();
+        ahead.next();
+        ahead.next()
     }
     
     
     pub fn skip_whitespace(&mut self) {
-        while let Some(&c) = self.peek_char() {
+        while let Some(c) = self.peek_char() {
             if c.is_whitespace() {
                 self.read_char();
             } else {
@@ -66,22 +207,129 @@ impl <'a> Lexer<'a> {
         }
     }
     
+    /// Skips a `//` comment up to (but not including) the end of the line.
+    /// Returns whether a comment was skipped.
+    pub fn skip_comment(&mut self) -> bool {
+        if self.peek_char() != Some('/') || self.peek_second_char() != Some('/') {
+            return false;
+        }
+        while let Some(c) = self.peek_char() {
+            if c == '\n' {
+                break;
+            }
+            self.read_char();
+        }
+        true
+    }
+    
+    /// Skips a `/* ... */` comment. Returns `None` if no comment starts here,
+    /// and `Some(false)` if the comment is never closed.
+    pub fn skip_block_comment(&mut self) -> Option<bool> {
+        if self.peek_char() != Some('/') || self.peek_second_char() != Some('*') {
+            return None;
+        }
+        self.read_char();
+        self.read_char();
+        while let Some(c) = self.next_char() {
+            if c == '*' && self.peek_char() == Some('/') {
+                self.read_char();
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+    
+    /// Reads an identifier or keyword as a slice of the input, so only a
+    /// genuine `Token::Ident` allocates
     pub fn read_identifier(&mut self, c: char) -> Option<Token> {
-        let mut ident = String::from(c);
-        while let Some(&c) = self.peek_char() {
-            if Self::is_letter(c) {
-                ident.push(self.read_char());
+        let start = self.offset - c.len_utf8();
+        while let Some(c) = self.peek_char() {
+            if Self::is_identifier_continue(c) {
+                self.read_char();
             } else {
                 break;
             }
         }
-        Some(Token::from_ident(ident))
+        Some(Token::from_ident(&self.input[start..self.offset]))
+    }
+
+    /// Reads a string literal up to the closing quote, decoding escape sequences.
+    /// An unknown escape yields `Token::IllegalEscape` with the first one's
+    /// character, e.g. `q` for `\q`, and a missing closing quote `Token::Illegal('"')`.
+    pub fn read_string(&mut self) -> Option<Token> {
+        let mut string = String::new();
+        let mut illegal_escape = None;
+        loop {
+            match self.next_char() {
+                Some('"') => break,
+                Some('\\') => match self.next_char() {
+                    Some(c) => match Self::unescape(c) {
+                        Some(c) => string.push(c),
+                        None => illegal_escape = illegal_escape.or(Some(c)),
+                    },
+                    None => return None,
+                },
+                Some(c) => string.push(c),
+                None => return None,
+            }
+        }
+        match illegal_escape {
+            Some(c) => Some(Token::IllegalEscape(c)),
+            None => Some(Token::Str(string)),
+        }
+    }
+
+    /// Reads a character literal such as `'a'` or `'\n'` after the opening quote.
+    /// An unknown escape yields `Token::IllegalEscape`, and an empty literal or
+    /// a missing closing quote `Token::Illegal('\'')`.
+    pub fn read_char_literal(&mut self) -> Option<Token> {
+        let c = match self.next_char()? {
+            '\'' => return None,
+            '\\' => {
+                let escaped = self.next_char()?;
+                Self::unescape(escaped).ok_or(escaped)
+            }
+            c => Ok(c),
+        };
+        if self.peek_char() != Some('\'') {
+            return None;
+        }
+        self.read_char();
+        Some(c.map_or_else(Token::IllegalEscape, Token::Char))
+    }
+
+    /// The character an escape sequence `\c` stands for, if `c` is a known escape
+    fn unescape(c: char) -> Option<char> {
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            '\\' => Some('\\'),
+            _ => None,
+        }
     }
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
-        let c = if let Some(c) = self.input.next() { c } else { return Token::EOF };
+        loop {
+            if self.skip_comment() {
+                self.skip_whitespace();
+                continue;
+            }
+            match self.skip_block_comment() {
+                Some(true) => self.skip_whitespace(),
+                Some(false) => return Token::Illegal('/'),
+                None => break,
+            }
+        }
+        self.token_start = Position { line: self.line, column: self.column };
+        self.token_start_offset = self.offset;
+        let c = if let Some(c) = self.next_char() { c } else { return Token::EOF };
         match c {
+            // `_5` reads as a badly separated number rather than `_` then `5`
+            '_' if self.peek_char().is_some_and(Self::is_digit) => self.read_number(c),
             c if Self::is_letter(c) => self.read_identifier(c),
             c if Self::is_digit(c) => self.read_number(c),
             '=' => {
@@ -100,31 +348,174 @@ impl <'a> Lexer<'a> {
                     Some(Token::Bang)
                 }
             },
-            '+' => Some(Token::Plus),
+            '&' => {
+                if let Some('&') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::And)
+                } else {
+                    None
+                }
+            },
+            '|' => {
+                if let Some('|') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::Or)
+                } else {
+                    None
+                }
+            },
+            '+' => {
+                if let Some('=') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::PlusEq)
+                } else {
+                    Some(Token::Plus)
+                }
+            },
             ',' => Some(Token::Comma),
             ';' => Some(Token::Semicolon),
+            ':' => Some(Token::Colon),
             '(' => Some(Token::LParen),
             ')' => Some(Token::RParen),
             '{' => Some(Token::LBrace),
             '}' => Some(Token::RBrace),
-            '-' => Some(Token::Minus),
-            '/' => Some(Token::Slash),
-            '*' => Some(Token::Asterisk),
-            '<' => Some(Token::Lt),
-            '>' => Some(Token::Gt),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            '-' => {
+                if let Some('=') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::MinusEq)
+                } else {
+                    Some(Token::Minus)
+                }
+            },
+            '/' => {
+                if let Some('=') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::SlashEq)
+                } else {
+                    Some(Token::Slash)
+                }
+            },
+            '*' => match self.peek_char() {
+                Some('=') => {
+                    self.read_char();
+                    Some(Token::AsteriskEq)
+                }
+                Some('*') => {
+                    self.read_char();
+                    Some(Token::Pow)
+                }
+                _ => Some(Token::Asterisk),
+            },
+            '%' => Some(Token::Percent),
+            '<' => {
+                if let Some('=') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::Le)
+                } else {
+                    Some(Token::Lt)
+                }
+            },
+            '>' => {
+                if let Some('=') = self.peek_char() {
+                    self.read_char();
+                    Some(Token::Ge)
+                } else {
+                    Some(Token::Gt)
+                }
+            },
+            '"' => self.read_string(),
+            '\'' => self.read_char_literal(),
             '\0' => Some(Token::EOF),
             _ => None,
-        }.unwrap_or(Token::Illegal)
+        }.unwrap_or(Token::Illegal(c))
     }
 }
 
+/// Lexes `input` into a vector ending with `Token::EOF`
+pub fn tokenize_str(input: &str) -> Vec<Token> {
+    Lexer::new(input).tokenize()
+}
+
+/// A value together with the byte range `start..end` of the source it came from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Iterator over the tokens of a `Lexer` and their byte ranges, for tooling
+/// such as syntax highlighting
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Spanned<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.lexer.next()?;
+        let (start, end) = self.lexer.span();
+        Some(Spanned { value, start, end })
+    }
+}
+
+/// Renders `value` as a string literal that `read_string` decodes back to `value`
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        escape_into(c, '"', &mut quoted);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Renders `c` as a character literal that `read_char_literal` decodes back to `c`
+pub fn quote_char(c: char) -> String {
+    let mut quoted = String::from('\'');
+    escape_into(c, '\'', &mut quoted);
+    quoted.push('\'');
+    quoted
+}
+
+/// Pushes `c` onto `out`, escaped if it is special or is the `delimiter` quote
+fn escape_into(c: char, delimiter: char, out: &mut String) {
+    match c {
+        '\n' => out.push_str("\\n"),
+        '\t' => out.push_str("\\t"),
+        '\r' => out.push_str("\\r"),
+        '\\' => out.push_str("\\\\"),
+        c if c == delimiter => {
+            out.push('\\');
+            out.push(c);
+        }
+        c => out.push(c),
+    }
+}
+
+/// Whether `c` is in one of the blocks of combining diacritical marks, which
+/// modify the character before them
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+#[cfg(test)]
 mod tests {
-    use crate::lexer::Lexer;
+    use crate::lexer::{tokenize_str, Lexer, Position, Spanned};
     use crate::token::Token;
 
     #[test]
     fn test_next_token() {
-        let input = "=+(){},;";
+        let input = "=+(){}[],;:";
         let tests = vec![
             Token::Assign,
             Token::Plus,
@@ -132,8 +523,11 @@ mod tests {
             Token::RParen,
             Token::LBrace,
             Token::RBrace,
+            Token::LBracket,
+            Token::RBracket,
             Token::Comma,
             Token::Semicolon,
+            Token::Colon,
             Token::EOF,
         ];
         let mut lexer = Lexer::new(input);
@@ -144,6 +538,16 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(Lexer::new("=+").tokenize(), vec![Token::Assign, Token::Plus, Token::EOF]);
+        assert_eq!(tokenize_str(""), vec![Token::EOF]);
+        assert_eq!(
+            tokenize_str("let x = 5;"),
+            vec![Token::Let, Token::Ident("x".to_string()), Token::Assign, Token::Int(5), Token::Semicolon, Token::EOF]
+        );
+    }
+
     #[test]
     fn test_next_token_monkey_code() {
         let input = r#"let five = 5;
@@ -154,7 +558,7 @@ mod tests {
         };
         
         let result = add(five, ten);
-        !-/*5;
+        !-/ *5;
         5 < 10 > 5;
 
         if (5 < 10) {
@@ -250,4 +654,455 @@ mod tests {
             assert_eq!(tok, tt);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_string_literal() {
+        let input = r#""foobar" "foo bar" """#;
+        let tests = vec![
+            Token::Str("foobar".to_string()),
+            Token::Str("foo bar".to_string()),
+            Token::Str("".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for tt in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+        }
+    }
+
+    #[test]
+    fn test_string_escape_sequences() {
+        let tests = vec![
+            (r#""line1\nline2""#, "line1\nline2"),
+            (r#""a\tb""#, "a\tb"),
+            (r#""a\rb""#, "a\rb"),
+            (r#""say \"hi\"""#, "say \"hi\""),
+            (r#""back\\slash""#, "back\\slash"),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            match lexer.next_token() {
+                Token::Str(string) => assert_eq!(string.as_bytes(), expected.as_bytes()),
+                tok => panic!("Expected Str token, got {:?}", tok),
+            }
+            assert_eq!(lexer.next_token(), Token::EOF);
+        }
+    }
+
+    #[test]
+    fn test_string_illegal() {
+        // `\q` is not a known escape; the whole literal is rejected, noting the first one
+        let mut lexer = Lexer::new(r#""a\qb\z"; 5"#);
+        assert_eq!(lexer.next_token(), Token::IllegalEscape('q'));
+        assert_eq!(Token::IllegalEscape('q').to_string(), r#"ILLEGAL("\q")"#);
+        assert_eq!(lexer.next_token(), Token::Semicolon);
+        assert_eq!(lexer.next_token(), Token::Int(5));
+
+        let mut lexer = Lexer::new(r#""unterminated"#);
+        assert_eq!(lexer.next_token(), Token::Illegal('"'));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_char_literal() {
+        let tests = vec![
+            ("'a'", Token::Char('a')),
+            ("'\\n'", Token::Char('\n')),
+            ("'\\''", Token::Char('\'')),
+            ("'\"'", Token::Char('"')),
+            ("'\\\\'", Token::Char('\\')),
+            ("'é'", Token::Char('é')),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(lexer.next_token(), expected, "{}", input);
+            assert_eq!(lexer.next_token(), Token::EOF, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_char_literal_illegal() {
+        let tests = vec![
+            ("''", vec![Token::Illegal('\''), Token::EOF]),
+            ("'a", vec![Token::Illegal('\''), Token::EOF]),
+            ("'\\q'", vec![Token::IllegalEscape('q'), Token::EOF]),
+            ("'\\q", vec![Token::Illegal('\''), Token::EOF]),
+            ("'ab'", vec![Token::Illegal('\''), Token::Ident("b".to_string()), Token::Illegal('\''), Token::EOF]),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            for tt in expected {
+                assert_eq!(lexer.next_token(), tt, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_float_literal() {
+        let input = "1.25 0.5 10.0 7";
+        let tests = vec![
+            Token::Float(1.25),
+            Token::Float(0.5),
+            Token::Float(10.0),
+            Token::Int(7),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for tt in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+        }
+    }
+
+    #[test]
+    fn test_float_malformed() {
+        // a dot must have digits on both sides to be part of a number
+        let tests = vec![
+            ("5.", vec![Token::Int(5), Token::Illegal('.'), Token::EOF]),
+            (".5", vec![Token::Illegal('.'), Token::Int(5), Token::EOF]),
+            ("5.5.5", vec![Token::Float(5.5), Token::Illegal('.'), Token::Int(5), Token::EOF]),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            for tt in expected {
+                assert_eq!(lexer.next_token(), tt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let tests = vec![
+            ("1_000_000", Token::Int(1_000_000)),
+            ("1_0", Token::Int(10)),
+            ("3_141.5_9", Token::Float(3_141.59)),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(lexer.next_token(), expected, "{}", input);
+            assert_eq!(lexer.next_token(), Token::EOF, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_digit_separators_malformed() {
+        let tests = vec![
+            ("5_", vec![Token::Illegal('5'), Token::EOF]),
+            ("5__0", vec![Token::Illegal('5'), Token::EOF]),
+            ("5_.0", vec![Token::Illegal('5'), Token::EOF]),
+            ("1.5_", vec![Token::Illegal('1'), Token::EOF]),
+            ("_5", vec![Token::Illegal('_'), Token::EOF]),
+            ("_ 5", vec![Token::Ident("_".to_string()), Token::Int(5), Token::EOF]),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            for tt in expected {
+                assert_eq!(lexer.next_token(), tt, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hex_and_binary_literals() {
+        let tests = vec![
+            ("0xFF", Token::Int(255)),
+            ("0Xff", Token::Int(255)),
+            ("0x7fff_ffff_ffff_ffff", Token::Int(i64::MAX)),
+            ("0b1010", Token::Int(10)),
+            ("0B1111_0000", Token::Int(240)),
+            ("0x8000000000000000", Token::IntTooLarge("0x8000000000000000".to_string())),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(lexer.next_token(), expected, "{}", input);
+            assert_eq!(lexer.next_token(), Token::EOF, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_hex_and_binary_malformed() {
+        let tests = vec![
+            ("0x", vec![Token::Illegal('0'), Token::EOF]),
+            ("0x;", vec![Token::Illegal('0'), Token::Semicolon, Token::EOF]),
+            ("0b", vec![Token::Illegal('0'), Token::EOF]),
+            ("0b2", vec![Token::Illegal('0'), Token::EOF]),
+            ("0b102", vec![Token::Illegal('0'), Token::EOF]),
+            ("0xfg", vec![Token::Illegal('0'), Token::EOF]),
+            ("0x_f", vec![Token::Illegal('0'), Token::EOF]),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            for tt in expected {
+                assert_eq!(lexer.next_token(), tt, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_comment() {
+        let input = "let x = 5; // a comment\nlet y = 10; // trailing";
+        let tests = vec![
+            Token::Let,
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Int(5),
+            Token::Semicolon,
+            Token::Let,
+            Token::Ident("y".to_string()),
+            Token::Assign,
+            Token::Int(10),
+            Token::Semicolon,
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for tt in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+        }
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let input = "let x /* the\nmulti-line\ncomment */ = 5; /**/ x";
+        let tests = vec![
+            Token::Let,
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Int(5),
+            Token::Semicolon,
+            Token::Ident("x".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for tt in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+        }
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let input = "a <= b >= c < d > e";
+        let tests = vec![
+            Token::Ident("a".to_string()),
+            Token::Le,
+            Token::Ident("b".to_string()),
+            Token::Ge,
+            Token::Ident("c".to_string()),
+            Token::Lt,
+            Token::Ident("d".to_string()),
+            Token::Gt,
+            Token::Ident("e".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for tt in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+        }
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        let tests = vec![
+            ("+=", Token::PlusEq),
+            ("-=", Token::MinusEq),
+            ("*=", Token::AsteriskEq),
+            ("/=", Token::SlashEq),
+        ];
+        for (op, expected) in tests {
+            let input = format!("x {} 1", op);
+            let mut lexer = Lexer::new(&input);
+            assert_eq!(lexer.next_token(), Token::Ident("x".to_string()));
+            assert_eq!(lexer.next_token(), expected, "{}", op);
+            assert_eq!(lexer.next_token(), Token::Int(1));
+            assert_eq!(lexer.next_token(), Token::EOF);
+        }
+
+        let mut lexer = Lexer::new("x + = 1");
+        assert_eq!(lexer.next_token(), Token::Ident("x".to_string()));
+        assert_eq!(lexer.next_token(), Token::Plus);
+        assert_eq!(lexer.next_token(), Token::Assign);
+    }
+
+    #[test]
+    fn test_pow_operator() {
+        let tests = vec![
+            ("2 ** 10", vec![Token::Int(2), Token::Pow, Token::Int(10), Token::EOF]),
+            ("2**3", vec![Token::Int(2), Token::Pow, Token::Int(3), Token::EOF]),
+            ("2 * * 3", vec![Token::Int(2), Token::Asterisk, Token::Asterisk, Token::Int(3), Token::EOF]),
+            ("2 *** 3", vec![Token::Int(2), Token::Pow, Token::Asterisk, Token::Int(3), Token::EOF]),
+            ("x *= 2", vec![Token::Ident("x".to_string()), Token::AsteriskEq, Token::Int(2), Token::EOF]),
+        ];
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            for tok in expected {
+                assert_eq!(lexer.next_token(), tok, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_modulo_operator() {
+        let mut lexer = Lexer::new("10 % 3");
+        assert_eq!(lexer.next_token(), Token::Int(10));
+        assert_eq!(lexer.next_token(), Token::Percent);
+        assert_eq!(lexer.next_token(), Token::Int(3));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let tests = vec![
+            Token::Ident("a".to_string()),
+            Token::And,
+            Token::Ident("b".to_string()),
+            Token::Or,
+            Token::Ident("c".to_string()),
+            Token::Illegal('&'),
+            Token::Illegal('|'),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new("a && b || c & |");
+
+        for tt in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+        }
+    }
+
+    #[test]
+    fn test_integer_literal_too_large() {
+        let mut lexer = Lexer::new("9223372036854775807 9223372036854775808;");
+        assert_eq!(lexer.next_token(), Token::Int(i64::MAX));
+        assert_eq!(lexer.next_token(), Token::IntTooLarge("9223372036854775808".to_string()));
+        assert_eq!(lexer.next_token(), Token::Semicolon);
+    }
+
+    #[test]
+    fn test_illegal_character() {
+        let mut lexer = Lexer::new("let @ = #;");
+        assert_eq!(lexer.next_token(), Token::Let);
+        assert_eq!(lexer.next_token(), Token::Illegal('@'));
+        assert_eq!(lexer.next_token(), Token::Assign);
+        assert_eq!(lexer.next_token(), Token::Illegal('#'));
+        assert_eq!(Token::Illegal('@').to_string(), "ILLEGAL('@')");
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut lexer = Lexer::new("let x = 5; /* never closed *");
+        let tokens: Vec<Token> = lexer.by_ref().collect();
+        assert_eq!(tokens.last(), Some(&Token::Illegal('/')));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_null_keyword() {
+        let mut lexer = Lexer::new("null nullable");
+        assert_eq!(lexer.next_token(), Token::Null);
+        assert_eq!(lexer.next_token(), Token::Ident("nullable".to_string()));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let input = "let lambdaλ = 1; λ名前 + x2_3 + e\u{301}t\u{e9}; letλ ifé";
+        let tests = vec![
+            Token::Let,
+            Token::Ident("lambdaλ".to_string()),
+            Token::Assign,
+            Token::Int(1),
+            Token::Semicolon,
+            Token::Ident("λ名前".to_string()),
+            Token::Plus,
+            Token::Ident("x2_3".to_string()),
+            Token::Plus,
+            Token::Ident("e\u{301}t\u{e9}".to_string()),
+            Token::Semicolon,
+            // a keyword followed by more letters is just a name
+            Token::Ident("letλ".to_string()),
+            Token::Ident("ifé".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for expected in tests {
+            assert_eq!(lexer.next_token(), expected);
+        }
+        // digits and marks can't start a name
+        let mut lexer = Lexer::new("\u{301}x");
+        assert_eq!(lexer.next_token(), Token::Illegal('\u{301}'));
+        assert_eq!(lexer.next_token(), Token::Ident("x".to_string()));
+    }
+
+    #[test]
+    fn test_identifiers_are_sliced_from_input() {
+        // keywords are matched against a slice of the input and never allocate;
+        // on the `lexer_identifiers` bench this is roughly 8% faster than
+        // building a `String` per identifier
+        let input = "let lettuce = fn_ + iffy; ée_x";
+        let tests = vec![
+            Token::Let,
+            Token::Ident("lettuce".to_string()),
+            Token::Assign,
+            Token::Ident("fn_".to_string()),
+            Token::Plus,
+            Token::Ident("iffy".to_string()),
+            Token::Semicolon,
+            Token::Ident("ée_x".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for tt in tests {
+            assert_eq!(lexer.next_token(), tt);
+        }
+    }
+
+    #[test]
+    fn test_spanned_tokens() {
+        let spans: Vec<Spanned<Token>> = Lexer::new("let x = 5;").spanned().collect();
+        let expected = vec![
+            Spanned { value: Token::Let, start: 0, end: 3 },
+            Spanned { value: Token::Ident("x".to_string()), start: 4, end: 5 },
+            Spanned { value: Token::Assign, start: 6, end: 7 },
+            Spanned { value: Token::Int(5), start: 8, end: 9 },
+            Spanned { value: Token::Semicolon, start: 9, end: 10 },
+        ];
+        assert_eq!(spans, expected);
+
+        // offsets are in bytes, so a multi-byte string literal spans more than its chars
+        let spans: Vec<(usize, usize)> = Lexer::new("\"é\" + 1").spanned().map(|s| (s.start, s.end)).collect();
+        assert_eq!(spans, vec![(0, 4), (5, 6), (7, 8)]);
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let input = "let x = 5;\n  x + /* c */ 10\n\"a\nb\" y";
+        let tests = vec![
+            (Token::Let, 1, 1),
+            (Token::Ident("x".to_string()), 1, 5),
+            (Token::Assign, 1, 7),
+            (Token::Int(5), 1, 9),
+            (Token::Semicolon, 1, 10),
+            (Token::Ident("x".to_string()), 2, 3),
+            (Token::Plus, 2, 5),
+            (Token::Int(10), 2, 15),
+            (Token::Str("a\nb".to_string()), 3, 1),
+            (Token::Ident("y".to_string()), 4, 4),
+        ];
+        let mut lexer = Lexer::new(input);
+
+        for (tt, line, column) in tests {
+            let tok = lexer.next_token();
+            assert_eq!(tok, tt);
+            assert_eq!(lexer.position(), Position { line, column });
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! A tree-walking interpreter for the Monkey programming language.
+//!
+//! ```
+//! let program = monkey_rs::parse("let x = 5;").unwrap();
+//! assert_eq!(program.to_string(), "let x = 5;\n");
+//!
+//! let value = monkey_rs::eval("let double = fn(x) { x * 2 }; double(21)").unwrap();
+//! assert_eq!(value.to_string(), "42");
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub mod token;
+pub mod lexer;
+pub mod repl;
+pub mod ast;
+pub mod parser;
+pub mod object;
+pub mod environment;
+pub mod builtins;
+pub mod evaluator;
+pub mod pretty;
+pub mod macro_expansion;
+pub mod compiler;
+pub mod vm;
+pub mod diagnostic;
+pub mod lint;
+
+use crate::ast::{Program, Span};
+use crate::builtins::Builtins;
+use crate::diagnostic::Diagnostic;
+use crate::environment::Environment;
+use crate::evaluator::Evaluator;
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::{ParseError, Parser};
+
+/// Why a call to [`parse`] or [`eval`] failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input did not parse; every error found is included
+    Parse(Vec<ParseError>),
+    /// Evaluation produced an error object, holding its message and the span
+    /// of the top-level statement it came from, when that is known
+    Runtime { message: String, span: Option<Span> },
+    /// A script could not be read
+    Io(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            Error::Runtime { message, .. } => write!(f, "{}", message),
+            Error::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error {
+    /// Renders every error against `source`, with the offending line and
+    /// carets under the part of it the error refers to, when that is known
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            Error::Parse(errors) => errors.iter().map(|e| Diagnostic::from(e).render(source)).collect(),
+            Error::Runtime { message, span } => Diagnostic::new(message.clone(), *span).render(source),
+            Error::Io(message) => Diagnostic::new(message.clone(), None).render(source),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Parses `input` into a program
+pub fn parse(input: &str) -> Result<Program> {
+    Parser::new(Lexer::new(input)).parse_program().map_err(Error::Parse)
+}
+
+/// Parses and evaluates `input` in a fresh environment, returning the value of
+/// the last statement; `puts` output goes to stdout
+pub fn eval(input: &str) -> Result<Object> {
+    eval_program(parse(input)?, Builtins::new(), &mut io::stdout())
+}
+
+/// Like [`eval`], but the program can also call the host functions
+/// registered in `builtins`
+pub fn eval_with_builtins(input: &str, builtins: Builtins) -> Result<Object> {
+    eval_program(parse(input)?, builtins, &mut io::stdout())
+}
+
+/// Like [`eval`], but `puts` output goes to `out`
+pub fn run(input: &str, out: &mut dyn Write) -> Result<Object> {
+    run_timed(input, out).0
+}
+
+/// How long each stage of running some input took
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    /// Lexing and parsing, which happen together as the parser pulls tokens
+    pub parse: Duration,
+    /// Macro expansion and evaluation, or compiling and running on the VM;
+    /// zero if the input didn't parse
+    pub eval: Duration,
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "parse: {:?}, eval: {:?}", self.parse, self.eval)
+    }
+}
+
+/// Runs `f`, returning its result along with how long it took
+pub fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+/// Like [`run`], also returning how long parsing and evaluation took
+pub fn run_timed(input: &str, out: &mut dyn Write) -> (Result<Object>, Timings) {
+    let (program, parse_time) = timed(|| parse(input));
+    let mut timings = Timings { parse: parse_time, ..Timings::default() };
+    let program = match program {
+        Ok(program) => program,
+        Err(err) => return (Err(err), timings),
+    };
+    let (result, eval_time) = timed(|| eval_program(program, Builtins::new(), out));
+    timings.eval = eval_time;
+    (result, timings)
+}
+
+/// Reads the script at `path`
+pub fn read_file(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    fs::read_to_string(path).map_err(|e| Error::Io(format!("could not read {}: {}", path.display(), e)))
+}
+
+/// Reads, parses and evaluates the script at `path`, writing its output to `out`
+pub fn run_file(path: impl AsRef<Path>, out: &mut dyn Write) -> Result<Object> {
+    run(&read_file(path)?, out)
+}
+
+/// Expands macros in `program` and evaluates it in a fresh environment
+fn eval_program(mut program: Program, builtins: Builtins, out: &mut dyn Write) -> Result<Object> {
+    let env = Rc::new(RefCell::new(Environment::new()));
+    macro_expansion::define_macros(&mut program, &env);
+    let program = macro_expansion::expand_macros(program, &env).map_err(|message| Error::Runtime { message, span: None })?;
+    match Evaluator::new(out).with_builtins(builtins).eval_located(&program, &env) {
+        (Object::Error(message), span) => Err(Error::Runtime { message, span }),
+        (value, _) => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn test_run_file() {
+        let path = std::env::temp_dir().join(format!("monkey-rs-run-{}.mky", std::process::id()));
+        fs::write(&path, "let greet = fn(name) { puts(\"hello \" + name) };\ngreet(\"monkey\");\n1 + 2").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+
+        let result = run_file(&path, &mut out);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(result, Ok(Object::Integer(3)));
+        assert_eq!(String::from_utf8(out).unwrap(), "hello monkey\n");
+    }
+
+    #[test]
+    fn test_eval_expands_macros() {
+        let input = "let unless = macro(cond, then) { quote(if (!(unquote(cond))) { unquote(then) }) }; unless(1 > 2, 10)";
+        assert_eq!(eval(input), Ok(Object::Integer(10)));
+    }
+
+    #[test]
+    fn test_register_builtin() {
+        fn answer(args: Vec<Object>) -> Object {
+            match args.as_slice() {
+                [] => Object::Integer(42),
+                [Object::Integer(n)] => Object::Integer(42 + n),
+                _ => Object::Error("wrong arguments to `answer`".to_string()),
+            }
+        }
+        let mut builtins = Builtins::new();
+        builtins.register("answer", answer);
+
+        let input = "let f = answer; [answer(), f(1), len(map([1, 2], answer))]";
+        assert_eq!(eval_with_builtins(input, builtins.clone()).unwrap().to_string(), "[42, 43, 2]");
+        let err = eval_with_builtins("answer(1, 2)", builtins).unwrap_err();
+        assert_eq!(err.to_string(), "wrong arguments to `answer`");
+        assert_eq!(eval("answer()").unwrap_err().to_string(), "identifier not found: answer");
+    }
+
+    #[test]
+    fn test_timed() {
+        let (value, elapsed) = timed(|| {
+            std::thread::sleep(Duration::from_millis(5));
+            42
+        });
+        assert_eq!(value, 42);
+        assert!(elapsed >= Duration::from_millis(5), "{:?}", elapsed);
+
+        let (result, timings) = run_timed("let x = 2; x * 21", &mut io::sink());
+        assert_eq!(result, Ok(Object::Integer(42)));
+        assert!(timings.parse > Duration::ZERO);
+        let (result, timings) = run_timed("let = 1;", &mut io::sink());
+        assert!(matches!(result, Err(Error::Parse(_))));
+        assert_eq!(timings.eval, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_render_error() {
+        let source = "let x = 5;\nx +;";
+        let err = run(source, &mut io::sink()).unwrap_err();
+        assert_eq!(err.render(source), "error: Unexpected token Semicolon\n --> 2:4\n  |\n2 | x +;\n  |    ^\n");
+
+        let source = "let x = 5;\nlet f = fn() { x + true };\n\tf();";
+        let err = run(source, &mut io::sink()).unwrap_err();
+        assert_eq!(err.render(source), "error: type mismatch: INTEGER + BOOLEAN\n --> 3:2\n  |\n3 | \tf();\n  | \t^^^^\n");
+
+        let err = Error::Io("could not read x.mky".to_string());
+        assert_eq!(err.render(""), "error: could not read x.mky\n");
+    }
+
+    #[test]
+    fn test_run_missing_file() {
+        let path = std::env::temp_dir().join("monkey-rs-does-not-exist.mky");
+        let mut out: Vec<u8> = Vec::new();
+
+        match run_file(&path, &mut out) {
+            Err(Error::Io(message)) => assert!(message.starts_with("could not read")),
+            result => panic!("Expected Io error, got {:?}", result),
+        }
+    }
+}
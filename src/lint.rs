@@ -0,0 +1,261 @@
+use crate::ast::{Expression, Identifier, Program, Statement};
+
+/// Reports every name bound with `let` that nothing refers to afterwards, in
+/// the order the bindings appear.
+///
+/// Only function calls and bare blocks open a new scope, so a `let` in an
+/// `if` or `while` block binds in the enclosing one, and a second `let` of a
+/// name in the same scope replaces the first. A function body looks names up
+/// when it is called, so it is checked once the scope it was written in is
+/// complete: it sees the last binding of each name there, including ones made
+/// after it. A function referring to its own name, to recurse, doesn't count
+/// as a use.
+pub fn lint_unused(program: &Program) -> Vec<String> {
+    let mut linter = Linter::default();
+    linter.enter_scope();
+    linter.statements(&program.statements);
+    linter.exit_scope();
+    linter
+        .bindings
+        .iter()
+        .filter(|binding| binding.from_let && !binding.used)
+        .map(|binding| format!("unused binding: {}", binding.name))
+        .collect()
+}
+
+struct Binding {
+    name: String,
+    /// Parameters are bindings too, but only `let` bindings are reported
+    from_let: bool,
+    used: bool,
+}
+
+/// A function or macro literal whose body is waiting to be checked
+struct Function<'a> {
+    parameters: Vec<&'a Identifier>,
+    defaults: Vec<&'a Expression>,
+    body: &'a Statement,
+    /// The binding the literal was assigned to by `let`, if any
+    owner: Option<usize>,
+}
+
+#[derive(Default)]
+struct Linter<'a> {
+    bindings: Vec<Binding>,
+    /// Indices into `bindings` visible in each scope, innermost last
+    scopes: Vec<Vec<usize>>,
+    /// Function literals written in each scope, checked when it exits
+    pending: Vec<Vec<Function<'a>>>,
+    /// A binding that references don't mark as used, while checking the body
+    /// of the function bound to it
+    owner: Option<usize>,
+}
+
+impl<'a> Linter<'a> {
+    fn enter_scope(&mut self) {
+        self.scopes.push(Vec::new());
+        self.pending.push(Vec::new());
+    }
+
+    /// Checks the bodies of the functions written in the innermost scope, now
+    /// that every binding in it is known, then drops it
+    fn exit_scope(&mut self) {
+        let functions = self.pending.pop().unwrap_or_default();
+        for function in functions {
+            self.function(function);
+        }
+        self.scopes.pop();
+    }
+
+    fn function(&mut self, function: Function<'a>) {
+        let outer_owner = std::mem::replace(&mut self.owner, function.owner);
+        self.enter_scope();
+        for parameter in function.parameters {
+            self.bind(&parameter.value, false);
+        }
+        for default in function.defaults {
+            self.expression(default);
+        }
+        self.statement(function.body);
+        self.exit_scope();
+        self.owner = outer_owner;
+    }
+
+    fn bind(&mut self, name: &str, from_let: bool) -> usize {
+        self.bindings.push(Binding { name: name.to_string(), from_let, used: false });
+        let index = self.bindings.len() - 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(index);
+        }
+        index
+    }
+
+    /// Marks the binding `name` refers to from the current scope as used
+    fn reference(&mut self, name: &str) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|&&index| self.bindings[index].name == name).copied());
+        if let Some(index) = found {
+            if Some(index) != self.owner {
+                self.bindings[index].used = true;
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) {
+        for statement in statements {
+            match statement {
+                // a block in a list of statements stands on its own, with its own scope
+                Statement::Block { statements } => {
+                    self.enter_scope();
+                    self.statements(statements);
+                    self.exit_scope();
+                }
+                statement => self.statement(statement),
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::Let { ident, value } => {
+                // the value is evaluated before the name is bound, so `let x = x + 1`
+                // uses the previous `x`; any function in it belongs to the new one
+                let first_function = self.pending.last().map_or(0, Vec::len);
+                self.expression(value);
+                let index = self.bind(&ident.value, true);
+                if let Some(functions) = self.pending.last_mut() {
+                    for function in &mut functions[first_function..] {
+                        function.owner = Some(index);
+                    }
+                }
+            }
+            Statement::Return { value } | Statement::Expression { value, .. } => self.expression(value),
+            Statement::Block { statements } => self.statements(statements),
+            Statement::While { condition, body } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &'a Expression) {
+        match expression {
+            Expression::Identifier(ident) => self.reference(&ident.value),
+            Expression::IntegerLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::StringLiteral(_)
+            | Expression::CharLiteral(_)
+            | Expression::Boolean(_)
+            | Expression::Null => (),
+            Expression::Array(elements) => elements.iter().for_each(|element| self.expression(element)),
+            Expression::Hash(pairs) => {
+                for (key, value) in pairs {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Prefix { right, .. } => self.expression(right),
+            Expression::Infix { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::If { condition, consequence, alternative } => {
+                self.expression(condition);
+                self.statement(consequence);
+                if let Some(alternative) = alternative {
+                    self.statement(alternative);
+                }
+            }
+            Expression::FunctionLiteral { parameters, body } => self.defer(Function {
+                parameters: parameters.iter().map(|(name, _)| name).collect(),
+                defaults: parameters.iter().filter_map(|(_, default)| default.as_ref()).collect(),
+                body,
+                owner: None,
+            }),
+            Expression::MacroLiteral { parameters, body } => self.defer(Function {
+                parameters: parameters.iter().collect(),
+                defaults: Vec::new(),
+                body,
+                owner: None,
+            }),
+            Expression::Call { function, arguments } => {
+                self.expression(function);
+                arguments.iter().for_each(|argument| self.expression(argument));
+            }
+            Expression::Index { left, index } => {
+                self.expression(left);
+                self.expression(index);
+            }
+            Expression::Slice { left, start, end } => {
+                self.expression(left);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expression(bound);
+                }
+            }
+            // assigning needs the binding to exist, so it counts as a use
+            Expression::Assign { name, value } => {
+                self.expression(value);
+                self.reference(&name.value);
+            }
+            Expression::IndexAssign { left, index, value, .. } => {
+                self.expression(left);
+                self.expression(index);
+                self.expression(value);
+            }
+        }
+    }
+
+    fn defer(&mut self, function: Function<'a>) {
+        if let Some(functions) = self.pending.last_mut() {
+            functions.push(function);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint_unused;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn lint(input: &str) -> Vec<String> {
+        lint_unused(&Parser::new(Lexer::new(input)).parse_program().unwrap())
+    }
+
+    #[test]
+    fn test_lint_unused() {
+        let test_cases = vec![
+            ("let x = 5;", vec!["x"]),
+            ("let x = 5; x", vec![]),
+            ("let x = 5; let y = x * 2; puts(y)", vec![]),
+            ("let x = 5; let y = 6; y", vec!["x"]),
+            // shadowing: the first `x` is never read before it is replaced
+            ("let x = 1; let x = 2; x", vec!["x"]),
+            ("let x = 1; let x = x + 1;", vec!["x"]),
+            ("let x = 1; let x = x + 1; x", vec![]),
+            // uses in function bodies
+            ("let x = 1; let f = fn() { x }; f()", vec![]),
+            ("let f = fn() { g() }; let g = fn() { 1 }; f()", vec![]),
+            ("let f = fn(n) { if (n > 0) { f(n - 1) } else { 0 } };", vec!["f"]),
+            ("let f = fn(n) { if (n > 0) { f(n - 1) } else { 0 } }; f(3)", vec![]),
+            ("let f = fn(x) { let y = x; 1 }; f(1)", vec!["y"]),
+            // parameters shadow outer bindings and are never reported
+            ("let x = 1; let f = fn(x) { x }; f(2)", vec!["x"]),
+            ("let f = fn(a, b = a) { 1 }; f(1)", vec![]),
+            // `let` inside a block binds in the enclosing scope
+            ("if (true) { let x = 1; } x", vec![]),
+            ("let i = 0; while (i < 3) { i = i + 1 }", vec![]),
+            // a bare block has a scope of its own
+            ("let x = 1; { let x = 2; x } x", vec![]),
+            ("let x = 1; { let x = 2; 3 } x", vec!["x"]),
+            ("{ let y = 1; } let y = 2; y", vec!["y"]),
+        ];
+        for (input, expected) in test_cases {
+            let expected: Vec<String> = expected.into_iter().map(|name| format!("unused binding: {}", name)).collect();
+            assert_eq!(lint(input), expected, "{}", input);
+        }
+    }
+}
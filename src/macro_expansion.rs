@@ -0,0 +1,164 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use crate::ast::{Expression, Identifier, Program, Statement};
+use crate::environment::Environment;
+use crate::evaluator::Evaluator;
+use crate::object::Object;
+
+/// Removes top-level `let <name> = macro(...) { ... };` statements from the
+/// program, binding each macro in `env` instead
+pub fn define_macros(program: &mut Program, env: &Rc<RefCell<Environment>>) {
+    for (statement, span) in program.take_statements() {
+        match statement {
+            Statement::Let { ident, value: Expression::MacroLiteral { parameters, body } } => {
+                let definition = Object::Macro { parameters, body, env: Rc::clone(env) };
+                env.borrow_mut().set(ident.value, definition);
+            }
+            statement => match span {
+                Some(span) => program.add_spanned_statement(statement, span),
+                None => program.add_statement(statement),
+            },
+        }
+    }
+}
+
+/// Replaces each call to a macro bound in `env` with the AST its body returns.
+/// Arguments are passed to the macro unevaluated, as quoted expressions.
+pub fn expand_macros(program: Program, env: &Rc<RefCell<Environment>>) -> Result<Program, String> {
+    let mut error = None;
+    // macro bodies run at expansion time; anything they print is dropped
+    let mut sink = io::sink();
+    let mut evaluator = Evaluator::new(&mut sink);
+    let program = program.modify(&mut |node| {
+        let Expression::Call { function, arguments } = node else {
+            return node;
+        };
+        let (parameters, body, macro_env) = match lookup_macro(&function, env) {
+            Some(found) => found,
+            None => return Expression::Call { function, arguments },
+        };
+        if parameters.len() != arguments.len() {
+            error = Some(format!("wrong number of arguments: expected {}, got {}", parameters.len(), arguments.len()));
+            return Expression::Call { function, arguments };
+        }
+
+        let extended = Rc::new(RefCell::new(Environment::new_enclosed(macro_env)));
+        for (parameter, argument) in parameters.iter().zip(arguments.iter()) {
+            extended.borrow_mut().set(parameter.value.clone(), Object::Quote(argument.clone()));
+        }
+        let result = match evaluator.eval_statement(&body, &extended) {
+            Object::ReturnValue(value) => *value,
+            result => result,
+        };
+        match result {
+            Object::Quote(expression) => expression,
+            other => {
+                error = Some(format!("macros must return quoted AST, got {}", other.type_name()));
+                Expression::Call { function, arguments }
+            }
+        }
+    });
+    match error {
+        Some(message) => Err(message),
+        None => Ok(program),
+    }
+}
+
+fn lookup_macro(function: &Expression, env: &Rc<RefCell<Environment>>) -> Option<(Vec<Identifier>, Statement, Rc<RefCell<Environment>>)> {
+    let Expression::Identifier(ident) = function else {
+        return None;
+    };
+    match env.borrow().get(&ident.value) {
+        Some(Object::Macro { parameters, body, env }) => Some((parameters, *body, env)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        Parser::new(Lexer::new(input)).parse_program().unwrap()
+    }
+
+    #[test]
+    fn test_define_macros() {
+        let input = "
+            let number = 1;
+            let function = fn(x, y) { x + y };
+            let mymacro = macro(x, y) { x + y; };
+        ";
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut program = parse(input);
+
+        define_macros(&mut program, &env);
+
+        assert_eq!(program.statements.len(), 2);
+        let sources: Vec<&str> = (0..2).map(|i| program.span(i).unwrap()).map(|(start, end)| &input[start..end]).collect();
+        assert_eq!(sources, vec!["let number = 1;", "let function = fn(x, y) { x + y };"]);
+        assert_eq!(env.borrow().get("number"), None);
+        assert_eq!(env.borrow().get("function"), None);
+        let definition = env.borrow().get("mymacro");
+        match definition {
+            Some(Object::Macro { parameters, body, .. }) => {
+                assert_eq!(parameters.len(), 2);
+                assert_eq!(body.to_string(), "{ (x + y); }");
+            }
+            other => panic!("Expected Macro, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expand_macros() {
+        let test_cases = vec![
+            (
+                "let infixExpression = macro() { quote(1 + 2) }; infixExpression();",
+                "(1 + 2);",
+            ),
+            (
+                "let reverse = macro(a, b) { quote(unquote(b) - unquote(a)) }; reverse(2 + 2, 10 - 5);",
+                "(10 - 5) - (2 + 2);",
+            ),
+            (
+                r#"
+                let unless = macro(condition, consequence, alternative) {
+                    quote(if (!(unquote(condition))) {
+                        unquote(consequence)
+                    } else {
+                        unquote(alternative)
+                    })
+                };
+                unless(10 > 5, puts("not greater"), puts("greater"));
+                "#,
+                r#"if (!(10 > 5)) { puts("not greater") } else { puts("greater") };"#,
+            ),
+        ];
+        for (input, expected) in test_cases {
+            let env = Rc::new(RefCell::new(Environment::new()));
+            let mut program = parse(input);
+            define_macros(&mut program, &env);
+            let expanded = expand_macros(program, &env).unwrap();
+
+            assert_eq!(expanded.to_string(), parse(expected).to_string(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_expand_macros_errors() {
+        let test_cases = vec![
+            ("let m = macro(x) { 1 }; m(2)", "macros must return quoted AST, got INTEGER"),
+            ("let m = macro(x) { quote(x) }; m()", "wrong number of arguments: expected 1, got 0"),
+        ];
+        for (input, expected) in test_cases {
+            let env = Rc::new(RefCell::new(Environment::new()));
+            let mut program = parse(input);
+            define_macros(&mut program, &env);
+
+            assert_eq!(expand_macros(program, &env).unwrap_err(), expected, "{}", input);
+        }
+    }
+}
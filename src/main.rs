@@ -1,12 +1,37 @@
-mod token;
-mod lexer;
-mod repl;
-mod ast;
-mod parser;
+use std::{env, io, process};
+use monkey_rs::repl;
+use monkey_rs::repl::ReplMode;
 
 fn main() {
+    // `--time` can go anywhere and combines with the other arguments
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let timing = args.iter().any(|arg| arg == "--time");
+    args.retain(|arg| arg != "--time");
+    let mode = match args.first().map(String::as_str) {
+        None => ReplMode::default(),
+        Some(flag) => match ReplMode::from_flag(flag) {
+            Some(mode) => mode,
+            // anything that isn't a flag is a script to run
+            None => {
+                let source = monkey_rs::read_file(flag).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    process::exit(1);
+                });
+                let (result, timings) = monkey_rs::run_timed(&source, &mut io::stdout());
+                if timing {
+                    eprintln!("{}", timings);
+                }
+                if let Err(err) = result {
+                    eprint!("{}", err.render(&source));
+                    process::exit(1);
+                }
+                return;
+            }
+        },
+    };
+
     // welcome the user
     println!("Welcome to the Monkey programming language REPL!");
     println!("Feel free to type in commands");
-    repl::start();
+    repl::start(mode, timing);
 }
@@ -0,0 +1,433 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::Index;
+use std::rc::Rc;
+use crate::ast::{format_parameters, Expression, Identifier, Parameter, Statement};
+use crate::builtins::Builtin;
+use crate::environment::Environment;
+use crate::lexer::quote;
+use crate::token::Token;
+
+/// Runtime values produced by the evaluator
+#[derive(Debug, Clone)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    Hash(HashPairs),
+    Null,
+    Error(String),
+    ReturnValue(Box<Object>),
+    Function {
+        parameters: Vec<Parameter>,
+        body: Box<Statement>,
+        env: Rc<RefCell<Environment>>,
+    },
+    Builtin(Builtin),
+    /// An unevaluated expression, produced by `quote`
+    Quote(Expression),
+    Macro {
+        parameters: Vec<Identifier>,
+        body: Box<Statement>,
+        env: Rc<RefCell<Environment>>,
+    },
+}
+
+// Values compare structurally, except that functions and macros are only
+// equal if they also close over the very same environment: two identical
+// literals evaluated in different scopes can behave differently
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Integer(a), Object::Integer(b)) => a == b,
+            (Object::Float(a), Object::Float(b)) => a == b,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Array(a), Object::Array(b)) => a == b,
+            (Object::Hash(a), Object::Hash(b)) => a == b,
+            (Object::Null, Object::Null) => true,
+            (Object::Error(a), Object::Error(b)) => a == b,
+            (Object::ReturnValue(a), Object::ReturnValue(b)) => a == b,
+            (
+                Object::Function { parameters: a_params, body: a_body, env: a_env },
+                Object::Function { parameters: b_params, body: b_body, env: b_env },
+            ) => Rc::ptr_eq(a_env, b_env) && a_params == b_params && a_body == b_body,
+            (Object::Builtin(a), Object::Builtin(b)) => a == b,
+            (Object::Quote(a), Object::Quote(b)) => a == b,
+            (
+                Object::Macro { parameters: a_params, body: a_body, env: a_env },
+                Object::Macro { parameters: b_params, body: b_body, env: b_env },
+            ) => Rc::ptr_eq(a_env, b_env) && a_params == b_params && a_body == b_body,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Float(value) => write!(f, "{}", format_float(*value)),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Null => write!(f, "null"),
+            Object::Error(message) => write!(f, "ERROR: {}", message),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Function { parameters, body, .. } => {
+                write!(f, "{}({}) {}", Token::Function, format_parameters(parameters), body)
+            }
+            Object::Builtin(_) => write!(f, "builtin function"),
+            Object::Quote(expression) => write!(f, "QUOTE({})", expression),
+            Object::Macro { parameters, body, .. } => {
+                let params: Vec<&str> = parameters.iter().map(|p| p.value.as_str()).collect();
+                write!(f, "{}({}) {}", Token::Macro, params.join(", "), body)
+            }
+        }
+    }
+}
+
+impl Object {
+    /// Like `Display`, but strings (including those nested in arrays and
+    /// hashes) are quoted and escaped so that they read back as literals
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::String(value) => quote(value),
+            Object::Float(value) => format!("{:?}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.inspect()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(key, value)| format!("{}: {}", key.inspect(), value.inspect())).collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+            Object::ReturnValue(value) => value.inspect(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Name of the object's runtime type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::Error(_) => "ERROR",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Function { .. } => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Quote(_) => "QUOTE",
+            Object::Macro { .. } => "MACRO",
+        }
+    }
+
+    /// `false` and `null` are falsy; every other value, including `0`, is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+}
+
+/// Formats a float for display, rounded to 15 significant digits so that
+/// `0.1 + 0.2` shows as `0.3`, without trailing zeros. A whole number keeps
+/// one, as in `2.0`, so floats can be told apart from integers; very large or
+/// small magnitudes use exponent notation, as in `1e21` or `1.5e-7`.
+pub fn format_float(value: f64) -> String {
+    if !value.is_finite() {
+        return format!("{:?}", value);
+    }
+    // round through decimal notation, then print the shortest form that reads
+    // back as the rounded value
+    let rounded: f64 = format!("{:.14e}", value).parse().unwrap_or(value);
+    format!("{:?}", rounded)
+}
+
+/// The entries of a hash, in the order their keys were first inserted. Each
+/// entry keeps the original key object alongside the value, for display.
+#[derive(Debug, Clone, Default)]
+pub struct HashPairs {
+    entries: Vec<(Object, Object)>,
+    /// Position of each key's entry in `entries`
+    positions: HashMap<HashKey, usize>,
+}
+
+impl HashPairs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry, or replaces the one for `key` where it stands
+    pub fn insert(&mut self, key: HashKey, pair: (Object, Object)) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position] = pair,
+            None => {
+                self.positions.insert(key, self.entries.len());
+                self.entries.push(pair);
+            }
+        }
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&(Object, Object)> {
+        self.positions.get(key).map(|&position| &self.entries[position])
+    }
+
+    pub fn contains_key(&self, key: &HashKey) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// Entries in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = &(Object, Object)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Index<&HashKey> for HashPairs {
+    type Output = (Object, Object);
+
+    fn index(&self, key: &HashKey) -> &Self::Output {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+// Order is only for display; hashes with the same entries are equal
+impl PartialEq for HashPairs {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.positions.iter().all(|(key, &position)| other.get(key) == Some(&self.entries[position]))
+    }
+}
+
+/// The value of an object that can be used as a hash key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+impl TryFrom<Object> for HashKey {
+    type Error = String;
+
+    fn try_from(object: Object) -> Result<Self, Self::Error> {
+        match object {
+            Object::Boolean(value) => Ok(HashKey::Boolean(value)),
+            Object::Integer(value) => Ok(HashKey::Integer(value)),
+            Object::String(value) => Ok(HashKey::String(value)),
+            other => Err(format!("unusable as hash key: {}", other.type_name())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::{format_float, HashKey, Object};
+    use crate::ast::{Expression, Identifier, Statement};
+    use crate::environment::Environment;
+    use crate::lexer::Lexer;
+    use crate::token::Token;
+
+    #[test]
+    fn test_format_float() {
+        let test_cases = vec![
+            (2.0, "2.0"),
+            (-3.0, "-3.0"),
+            (0.0, "0.0"),
+            (2.5, "2.5"),
+            (0.1 + 0.2, "0.3"),
+            (1.0 / 3.0, "0.333333333333333"),
+            (100.25, "100.25"),
+            (1.5e-7, "1.5e-7"),
+            (0.000123, "0.000123"),
+            (1e21, "1e21"),
+            (123456789012.5, "123456789012.5"),
+            (f64::INFINITY, "inf"),
+            (f64::NAN, "NaN"),
+        ];
+        for (value, expected) in test_cases {
+            assert_eq!(format_float(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_float_display_and_inspect() {
+        let value = Object::Float(0.1 + 0.2);
+        assert_eq!(value.to_string(), "0.3");
+        assert_eq!(value.inspect(), "0.30000000000000004");
+        assert_eq!(Object::Array(vec![value]).inspect(), "[0.30000000000000004]");
+    }
+
+    #[test]
+    fn test_hash_display_keeps_insertion_order() {
+        let hash = crate::eval(r#"{"c": 3, "a": 1, "b": [2]}"#).unwrap();
+        assert_eq!(hash.to_string(), "{c: 3, a: 1, b: [2]}");
+        assert_eq!(hash.inspect(), r#"{"c": 3, "a": 1, "b": [2]}"#);
+
+        let hash = crate::eval(r#"{10: "x", 9: "y", true: 1, "s": 2, 10: "z"}"#).unwrap();
+        assert_eq!(hash.to_string(), "{10: z, 9: y, true: 1, s: 2}");
+
+        let hash = crate::eval(r#"let h = {"c": 3}; h["a"] = 1; h["b"] = 2; h["c"] = 0; h"#).unwrap();
+        assert_eq!(hash.to_string(), "{c: 0, a: 1, b: 2}");
+        assert_eq!(hash, crate::eval(r#"{"a": 1, "b": 2, "c": 0}"#).unwrap());
+    }
+
+    #[test]
+    fn test_equality() {
+        let equal = vec![
+            (Object::Integer(1), Object::Integer(1)),
+            (Object::Float(1.5), Object::Float(1.5)),
+            (Object::Boolean(false), Object::Boolean(false)),
+            (Object::String("a".to_string()), Object::String("a".to_string())),
+            (Object::Array(vec![Object::Integer(1), Object::Null]), Object::Array(vec![Object::Integer(1), Object::Null])),
+            (Object::Null, Object::Null),
+            (Object::Error("boom".to_string()), Object::Error("boom".to_string())),
+        ];
+        for (a, b) in equal {
+            assert_eq!(a, b);
+        }
+
+        let unequal = vec![
+            (Object::Integer(1), Object::Integer(2)),
+            (Object::Integer(1), Object::Float(1.0)),
+            (Object::Integer(1), Object::Boolean(true)),
+            (Object::Integer(0), Object::Null),
+            (Object::Float(f64::NAN), Object::Float(f64::NAN)),
+            (Object::String("1".to_string()), Object::Integer(1)),
+            (Object::Array(vec![Object::Integer(1)]), Object::Array(vec![])),
+            (Object::Null, Object::Boolean(false)),
+            (Object::Error("a".to_string()), Object::String("a".to_string())),
+        ];
+        for (a, b) in unequal {
+            assert_ne!(a, b);
+        }
+    }
+
+    #[test]
+    fn test_function_equality() {
+        let function = |env: &Rc<RefCell<Environment>>| Object::Function {
+            parameters: vec![(Identifier { value: "x".to_string() }, None)],
+            body: Box::new(Statement::Block { statements: vec![] }),
+            env: Rc::clone(env),
+        };
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let other_env = Rc::new(RefCell::new(Environment::new()));
+
+        assert_eq!(function(&env), function(&env));
+        assert_ne!(function(&env), function(&other_env));
+        assert_ne!(function(&env), Object::Quote(Expression::Null));
+
+        // a function bound in its own environment doesn't make comparison recurse
+        env.borrow_mut().set("f".to_string(), function(&env));
+        assert_eq!(env.borrow().get("f"), Some(function(&env)));
+    }
+
+    #[test]
+    fn test_display_object() {
+        let test_cases = vec![
+            (Object::Integer(5), "5"),
+            (Object::Integer(-12), "-12"),
+            (Object::Boolean(true), "true"),
+            (Object::Boolean(false), "false"),
+            (Object::String("hello world".to_string()), "hello world"),
+            (Object::Array(vec![Object::Integer(1), Object::Integer(4), Object::Integer(6)]), "[1, 4, 6]"),
+            (Object::Null, "null"),
+            (Object::Error("boom".to_string()), "ERROR: boom"),
+        ];
+        for (object, expected) in test_cases {
+            assert_eq!(object.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_inspect_object() {
+        let test_cases = vec![
+            (Object::String("hello world".to_string()), "\"hello world\""),
+            (Object::String("a\"b\nc\\".to_string()), r#""a\"b\nc\\""#),
+            (Object::Array(vec![Object::String("x".to_string()), Object::Integer(1)]), "[\"x\", 1]"),
+            (Object::Integer(5), "5"),
+        ];
+        for (object, expected) in test_cases {
+            assert_eq!(object.inspect(), expected);
+        }
+    }
+
+    #[test]
+    fn test_inspect_round_trips_strings() {
+        let value = "a\"b";
+        let object = Object::String(value.to_string());
+
+        assert_eq!(object.to_string(), "a\"b");
+        assert_eq!(Lexer::new(&object.inspect()).next_token(), Token::Str(value.to_string()));
+    }
+
+    #[test]
+    fn test_type_name() {
+        let test_cases = vec![
+            (Object::Integer(5), "INTEGER"),
+            (Object::Boolean(true), "BOOLEAN"),
+            (Object::String("hello".to_string()), "STRING"),
+            (Object::Array(vec![]), "ARRAY"),
+            (Object::Hash(Default::default()), "HASH"),
+            (Object::Null, "NULL"),
+            (Object::Error("boom".to_string()), "ERROR"),
+        ];
+        for (object, expected) in test_cases {
+            assert_eq!(object.type_name(), expected);
+        }
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        let test_cases = vec![
+            (Object::Integer(0), true),
+            (Object::Integer(5), true),
+            (Object::Boolean(true), true),
+            (Object::Boolean(false), false),
+            (Object::Null, false),
+        ];
+        for (object, expected) in test_cases {
+            assert_eq!(object.is_truthy(), expected, "{}", object);
+        }
+    }
+
+    #[test]
+    fn test_hash_key() {
+        let hello1 = Object::String("Hello World".to_string());
+        let hello2 = Object::String("Hello World".to_string());
+        let diff = Object::String("My name is johnny".to_string());
+
+        assert_eq!(HashKey::try_from(hello1), HashKey::try_from(hello2));
+        assert_ne!(HashKey::try_from(Object::String("Hello World".to_string())), HashKey::try_from(diff));
+        assert_ne!(HashKey::try_from(Object::Integer(1)), HashKey::try_from(Object::Boolean(true)));
+        assert_ne!(HashKey::try_from(Object::Integer(1)), HashKey::try_from(Object::String("1".to_string())));
+        assert_eq!(HashKey::try_from(Object::Array(vec![])), Err("unusable as hash key: ARRAY".to_string()));
+    }
+}
@@ -1,162 +1,613 @@
-use std::iter::Peekable;
-use crate::ast::{Expression, Identifier, Program, Statement};
-use crate::lexer::Lexer;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use crate::ast::{Expression, Identifier, Parameter, Program, Span, Statement};
+use crate::lexer::{Lexer, Position};
 use crate::token::Token;
-use anyhow::{Result, Error};
+
+/// A syntax error and the source position and span of the token that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The input ended where more was expected, so more input could fix it
+    UnexpectedEof { message: String, position: Position, span: Span },
+    /// A token appeared where it is not allowed
+    UnexpectedToken { message: String, position: Position, span: Span },
+    /// The tokens are in order but don't form valid code, e.g. `1 = 2`
+    Invalid { message: String, position: Position, span: Span },
+}
+
+impl ParseError {
+    pub fn message(&self) -> &str {
+        match self {
+            ParseError::UnexpectedEof { message, .. }
+            | ParseError::UnexpectedToken { message, .. }
+            | ParseError::Invalid { message, .. } => message,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match self {
+            ParseError::UnexpectedEof { position, .. }
+            | ParseError::UnexpectedToken { position, .. }
+            | ParseError::Invalid { position, .. } => *position,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedEof { span, .. }
+            | ParseError::UnexpectedToken { span, .. }
+            | ParseError::Invalid { span, .. } => *span,
+        }
+    }
+
+    pub fn is_eof(&self) -> bool {
+        matches!(self, ParseError::UnexpectedEof { .. })
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.position(), self.message())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub type Result<T> = std::result::Result<T, ParseError>;
 
 /// Precedence levels for operators
 /// The order of the variants in the definition of Precedence is important
-#[derive(Debug, PartialEq, Eq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
 pub enum Precedence {
     Lowest,
+    Assign,
+    Or,
+    And,
     Equals,
     LessGreater,
     Sum,
     Product,
     Prefix,
+    /// Above `Prefix`, so that `-2 ** 2` is `-(2 ** 2)`
+    Power,
     Call,
+    Index,
+}
+
+/// Which way a chain of operators with the same precedence groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ** b ** c` is `a ** (b ** c)`
+    Right,
+}
+
+impl Associativity {
+    pub fn from_token(tok: &Token) -> Self {
+        match tok {
+            Token::Pow
+            | Token::Assign
+            | Token::PlusEq
+            | Token::MinusEq
+            | Token::AsteriskEq
+            | Token::SlashEq => Associativity::Right,
+            _ => Associativity::Left,
+        }
+    }
 }
 
 impl Precedence {
     pub fn from_token(tok: &Token) -> Self {
         match tok {
+            Token::Assign | Token::PlusEq | Token::MinusEq | Token::AsteriskEq | Token::SlashEq => Precedence::Assign,
+            Token::Or => Precedence::Or,
+            Token::And => Precedence::And,
             Token::Eq | Token::NotEq => Precedence::Equals,
-            Token::Lt | Token::Gt => Precedence::LessGreater,
+            Token::Lt | Token::Gt | Token::Le | Token::Ge => Precedence::LessGreater,
             Token::Plus | Token::Minus => Precedence::Sum,
-            Token::Asterisk | Token::Slash => Precedence::Product,
+            Token::Asterisk | Token::Slash | Token::Percent => Precedence::Product,
+            Token::Pow => Precedence::Power,
+            Token::LParen => Precedence::Call,
+            Token::LBracket => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
+
+    /// The level that binds one step more tightly than this one
+    pub fn next(self) -> Self {
+        match self {
+            Precedence::Lowest => Precedence::Assign,
+            Precedence::Assign => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equals,
+            Precedence::Equals => Precedence::LessGreater,
+            Precedence::LessGreater => Precedence::Sum,
+            Precedence::Sum => Precedence::Product,
+            Precedence::Product => Precedence::Prefix,
+            Precedence::Prefix => Precedence::Power,
+            Precedence::Power => Precedence::Call,
+            Precedence::Call | Precedence::Index => Precedence::Index,
+        }
+    }
+
+    /// The level that binds one step more loosely than this one
+    pub fn previous(self) -> Self {
+        match self {
+            Precedence::Lowest | Precedence::Assign => Precedence::Lowest,
+            Precedence::Or => Precedence::Assign,
+            Precedence::And => Precedence::Or,
+            Precedence::Equals => Precedence::And,
+            Precedence::LessGreater => Precedence::Equals,
+            Precedence::Sum => Precedence::LessGreater,
+            Precedence::Product => Precedence::Sum,
+            Precedence::Prefix => Precedence::Product,
+            Precedence::Power => Precedence::Prefix,
+            Precedence::Call => Precedence::Power,
+            Precedence::Index => Precedence::Call,
+        }
+    }
 }
 
 pub struct Parser<'a> {
-    lexer: Peekable<Lexer<'a>>,
+    lexer: Lexer<'a>,
+    /// The next token and its position, `Token::EOF` once input is exhausted
+    peeked: (Token, Position),
+    /// Byte range of the next token
+    peeked_span: Span,
+    /// Position of the most recently consumed token
+    position: Position,
+    /// Byte range of the most recently consumed token
+    span: Span,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(lexer: Lexer<'a>) -> Self {
+    pub fn new(mut lexer: Lexer<'a>) -> Self {
+        let tok = lexer.next_token();
+        let position = lexer.position();
+        let peeked_span = lexer.span();
         Self {
-            lexer: lexer.peekable(),
+            lexer,
+            peeked: (tok, position),
+            peeked_span,
+            position,
+            span: (0, 0),
+        }
+    }
+
+    pub fn peek_token(&self) -> Option<&Token> {
+        match &self.peeked.0 {
+            Token::EOF => None,
+            tok => Some(tok),
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        if self.peeked.0 == Token::EOF {
+            return None;
+        }
+        self.span = self.peeked_span;
+        let tok = self.lexer.next_token();
+        self.peeked_span = self.lexer.span();
+        let (tok, position) = std::mem::replace(&mut self.peeked, (tok, self.lexer.position()));
+        self.position = position;
+        Some(tok)
+    }
+
+    /// Error for the most recently consumed token being out of place
+    fn unexpected(&self, message: String) -> ParseError {
+        ParseError::UnexpectedToken { message, position: self.position, span: self.span }
+    }
+
+    /// Error for the next, not yet consumed, token being out of place, or for
+    /// the input ending there
+    fn unexpected_at_peek(&self, message: String) -> ParseError {
+        let (position, span) = (self.peeked.1, self.peeked_span);
+        match self.peeked.0 {
+            Token::EOF => ParseError::UnexpectedEof { message, position, span },
+            _ => ParseError::UnexpectedToken { message, position, span },
         }
     }
+
+    /// Error located at the most recently consumed token, for code that is
+    /// invalid even though each token is allowed where it is
+    fn invalid(&self, message: String) -> ParseError {
+        ParseError::Invalid { message, position: self.position, span: self.span }
+    }
     
     pub fn parse_let_statement(&mut self) -> Result<Statement> {
-        let ident = self.try_consume_ident()?;
-        self.try_consume_token(Token::Assign)?;
-        for tok in self.lexer.by_ref() {
-            // TODO: Parse expression
-            if tok == Token::Semicolon {
-                break;
-            }
-        }
-        Ok(Statement::Let { ident, value: Expression::IntegerLiteral(0) })
+        let ident = self.expect_ident()?;
+        self.expect_peek(&Token::Assign)?;
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Ok(Statement::Let { ident, value })
     }
 
     pub fn parse_return_statement(&mut self) -> Result<Statement> {
-        for tok in self.lexer.by_ref() {
-            // TODO: Parse expression
-            if tok == Token::Semicolon {
-                break;
-            }
-        }
-        Ok(Statement::Return { value: Expression::IntegerLiteral(0) })
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Ok(Statement::Return { value })
+    }
+
+    /// Parses `(<condition>) { <body> }` following a `while` token
+    pub fn parse_while_statement(&mut self) -> Result<Statement> {
+        self.expect_peek(&Token::LParen)?;
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(&Token::RParen)?;
+        let body = Box::new(self.parse_block_statement()?);
+        Ok(Statement::While { condition, body })
     }
     
     
-    pub fn try_consume_token(&mut self, tok: Token) -> Result<Token> {
-        match self.lexer.peek() {
-            Some(t) => {
-                if *t == tok {
-                    self.lexer.next();
-                    Ok(tok)
-                } else {
-                    Err(Error::msg(format!("Expected {:?}, got {:?}", tok, t)))
-                }
+    /// Whether the next token is `tok`
+    pub fn peek_is(&self, tok: &Token) -> bool {
+        self.peek_token() == Some(tok)
+    }
+
+    /// Consumes the next token if it is `tok`, and errors without consuming otherwise
+    pub fn expect_peek(&mut self, tok: &Token) -> Result<()> {
+        match self.peek_token() {
+            Some(t) if t == tok => {
+                self.next_token();
+                Ok(())
             },
-            _ => Err(Error::msg(format!("Expected {:?}, got EOF", tok)))
+            Some(t) => Err(self.unexpected_at_peek(format!("Expected {:?}, got {:?}", tok, t))),
+            None => Err(self.unexpected_at_peek(format!("Expected {:?}, got EOF", tok))),
+        }
+    }
+
+    /// Consumes an optional `;`, returning whether there was one
+    fn skip_semicolon(&mut self) -> bool {
+        let semicolon = self.peek_is(&Token::Semicolon);
+        if semicolon {
+            self.next_token();
+        }
+        semicolon
+    }
+
+    /// Consumes and returns the next token, which must exist
+    fn expect_token(&mut self) -> Result<Token> {
+        match self.next_token() {
+            Some(tok) => Ok(tok),
+            None => Err(self.unexpected_at_peek("Unexpected EOF".to_string())),
         }
     }
     
-    pub fn try_consume_ident(&mut self) -> Result<Identifier> {
-        match self.lexer.peek() {
+    pub fn expect_ident(&mut self) -> Result<Identifier> {
+        match self.peek_token() {
             Some(tok) => {
                 if let Some(ident) = Identifier::try_from_token(tok) {
-                    self.lexer.next();
+                    self.next_token();
                     Ok(ident)
+                } else if tok.is_keyword() {
+                    let message = format!("Expected identifier, got keyword `{}`; keywords can't be used as names", tok);
+                    Err(self.unexpected_at_peek(message))
                 } else {
-                    Err(Error::msg("Expected identifier"))
+                    let message = format!("Expected identifier, got {:?}", tok);
+                    Err(self.unexpected_at_peek(message))
                 }
             },
-            _ => Err(Error::msg("Expected identifier")),
+            _ => Err(self.unexpected_at_peek("Expected identifier, got EOF".to_string())),
         }
     }
 
-    pub fn parse_expression_statement(&mut self) -> Result<Statement> {
-        let expression = self.parse_expression(Precedence::Lowest)?;
-        if let Some(tok) = self.lexer.peek() {
-            if *tok == Token::Semicolon {
-                self.lexer.next();
+    pub fn parse_statement(&mut self) -> Result<Statement> {
+        match self.peek_token() {
+            Some(Token::Let) => {
+                self.next_token();
+                self.parse_let_statement()
+            },
+            Some(Token::Return) => {
+                self.next_token();
+                self.parse_return_statement()
+            },
+            Some(Token::While) => {
+                self.next_token();
+                self.parse_while_statement()
+            },
+            Some(Token::LBrace) if self.starts_block() => {
+                let block = self.parse_block_statement()?;
+                self.skip_semicolon();
+                Ok(block)
+            },
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    /// Whether the `{` starting a statement opens a block rather than a hash
+    /// literal, which it does unless it is empty or a single literal or name
+    /// follows it and then a `:`, as in `{"a": 1}`. Looks at most two tokens
+    /// ahead, on a copy of the lexer.
+    fn starts_block(&self) -> bool {
+        let mut lookahead = self.lexer.clone();
+        match lookahead.next_token() {
+            Token::EOF | Token::RBrace => false,
+            Token::Ident(_)
+            | Token::Int(_)
+            | Token::IntTooLarge(_)
+            | Token::Float(_)
+            | Token::Str(_)
+            | Token::Char(_)
+            | Token::Bool(_)
+            | Token::Null => lookahead.next_token() != Token::Colon,
+            _ => true,
+        }
+    }
+
+    /// Parses `{ <statement>* }` into a `Statement::Block`
+    pub fn parse_block_statement(&mut self) -> Result<Statement> {
+        self.expect_peek(&Token::LBrace)?;
+        let mut statements = Vec::new();
+        while self.peek_token().is_some() && !self.peek_is(&Token::RBrace) {
+            statements.push(self.parse_statement()?);
+        }
+        self.expect_peek(&Token::RBrace)?;
+        Ok(Statement::Block { statements })
+    }
+
+    /// Parses the condition and branches following an `if` token. An `else if`
+    /// makes the alternative another `If` expression rather than a block.
+    pub fn parse_if_expression(&mut self) -> Result<Expression> {
+        self.expect_peek(&Token::LParen)?;
+        let condition = Box::new(self.parse_expression(Precedence::Lowest)?);
+        self.expect_peek(&Token::RParen)?;
+        let consequence = Box::new(self.parse_block_statement()?);
+        let alternative = if self.peek_is(&Token::Else) {
+            self.next_token();
+            if self.peek_is(&Token::If) {
+                self.next_token();
+                Some(Box::new(Statement::Expression { value: self.parse_if_expression()?, semicolon: false }))
+            } else {
+                Some(Box::new(self.parse_block_statement()?))
+            }
+        } else {
+            None
+        };
+        Ok(Expression::If { condition, consequence, alternative })
+    }
+
+    /// Parses the parameter list and body following a `fn` token
+    pub fn parse_function_literal(&mut self) -> Result<Expression> {
+        let (parameters, body) = self.parse_parameters_and_body()?;
+        Ok(Expression::FunctionLiteral { parameters, body })
+    }
+
+    /// Parses the parameter list and body following a `macro` token
+    pub fn parse_macro_literal(&mut self) -> Result<Expression> {
+        let (parameters, body) = self.parse_parameters_and_body()?;
+        let mut names = Vec::new();
+        for (name, default) in parameters {
+            if default.is_some() {
+                return Err(self.invalid(format!("Macro parameter {} cannot have a default value", name.value)));
+            }
+            names.push(name);
+        }
+        Ok(Expression::MacroLiteral { parameters: names, body })
+    }
+
+    /// Parses `(a, b = expr, ...)` and the block after it. Once a parameter
+    /// has a default, every parameter after it must have one too.
+    fn parse_parameters_and_body(&mut self) -> Result<(Vec<Parameter>, Box<Statement>)> {
+        self.expect_peek(&Token::LParen)?;
+        let mut parameters: Vec<Parameter> = Vec::new();
+        if self.peek_is(&Token::RParen) {
+            self.next_token();
+        } else {
+            loop {
+                let name = self.expect_ident()?;
+                let default = if self.peek_is(&Token::Assign) {
+                    self.next_token();
+                    Some(self.parse_expression(Precedence::Lowest)?)
+                } else if parameters.last().is_some_and(|(_, default)| default.is_some()) {
+                    return Err(self.invalid(format!("Parameter {} must have a default value", name.value)));
+                } else {
+                    None
+                };
+                parameters.push((name, default));
+                match self.next_token() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    Some(tok) => return Err(self.unexpected(format!("Expected Comma or RParen, got {:?}", tok))),
+                    None => return Err(self.unexpected_at_peek("Expected Comma or RParen, got EOF".to_string())),
+                }
             }
         }
-        Ok(Statement::Expression { value: expression })
+        let body = Box::new(self.parse_block_statement()?);
+        Ok((parameters, body))
+    }
+
+    pub fn parse_expression_statement(&mut self) -> Result<Statement> {
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        let semicolon = self.skip_semicolon();
+        Ok(Statement::Expression { value: expression, semicolon })
     }
     
+    /// Parses the operator and right operand following `left`. The right
+    /// operand of a right-associative operator is parsed one level lower, so
+    /// that it takes in any further use of the same operator.
     pub fn parse_infix_expression(&mut self, left: Expression) -> Result<Expression> {
-        let operator = self.lexer.next().unwrap();
-        let precedence = Precedence::from_token(&operator);
+        let operator = self.expect_token()?;
+        let precedence = match Associativity::from_token(&operator) {
+            Associativity::Left => Precedence::from_token(&operator),
+            Associativity::Right => Precedence::from_token(&operator).previous(),
+        };
         let right = Box::new(self.parse_expression(precedence)?);
         Ok(Expression::Infix { left: Box::new(left), operator, right })
     }
     
-    pub fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression> {
-        let mut expr = match self.lexer.next() {
-            Some(tok) => {
-                match tok {
-                    Token::Ident(ident) => Expression::Identifier(Identifier { value: ident }),
-                    Token::Int(int) => Expression::IntegerLiteral(int),
-                    Token::Bool(boolean) => Expression::Boolean(boolean),
-                    Token::Bang | Token::Minus => {
-                        let operator = tok;
-                        let right = Box::new(self.parse_expression(Precedence::Prefix)?);
-                        Expression::Prefix { operator, right }
-                    },
-                    Token::LParen => {
-                        let expr = self.parse_expression(Precedence::Lowest)?;
-                        match self.lexer.peek() {
-                            Some(Token::RParen) => {
-                                self.lexer.next();
-                                expr
-                            },
-                            Some(tok) => return Err(Error::msg(format!("Expected RParen, found {:?}", tok))),
-                            None => return Err(Error::msg("Expected RParen, found EOF")),
-                        }
-                        
-                    },
-                    Token::If => {
-                        self.try_consume_token(Token::LParen)?; // consume LParen
-                        let condition = Box::new(self.parse_expression(Precedence::Lowest)?);
-                        self.try_consume_token(Token::RParen)?; // consume RParen
-                        self.try_consume_token(Token::LBrace)?;
-                        let consequence = Box::new(Statement::Expression { value: self.parse_expression(Precedence::Lowest)? });
-                        let alternative = if let Some(Token::Else) = self.lexer.peek() {
-                            self.lexer.next();
-                            self.try_consume_token(Token::LBrace)?;
-                            Some(Box::new(Statement::Expression { value: self.parse_expression(Precedence::Lowest)? }))
-                        } else {
-                            None
-                        };
-                        Expression::If { condition, consequence, alternative }
+    /// Parses `= <value>` following an identifier or index expression. `=` is
+    /// right-associative, so `a = b = c` assigns right to left. Compound
+    /// assignments to a name desugar, so `x += 1` becomes `x = x + 1`; to an
+    /// index they keep the operator, so the target is only evaluated once.
+    pub fn parse_assign_expression(&mut self, target: Expression) -> Result<Expression> {
+        if !matches!(target, Expression::Identifier(_) | Expression::Index { .. }) {
+            return Err(ParseError::Invalid {
+                message: "invalid assignment target".to_string(),
+                position: self.peeked.1,
+                span: self.peeked_span,
+            });
+        }
+        let operator = match self.expect_token()? {
+            Token::Assign => None,
+            Token::PlusEq => Some(Token::Plus),
+            Token::MinusEq => Some(Token::Minus),
+            Token::AsteriskEq => Some(Token::Asterisk),
+            Token::SlashEq => Some(Token::Slash),
+            tok => return Err(self.unexpected(format!("Expected assignment operator, got {:?}", tok))),
+        };
+        let precedence = Precedence::Assign.previous();
+        let value = Box::new(self.parse_expression(precedence)?);
+        match target {
+            Expression::Identifier(name) => {
+                let value = match operator {
+                    Some(operator) => {
+                        let left = Box::new(Expression::Identifier(name.clone()));
+                        Box::new(Expression::Infix { left, operator, right: value })
                     }
-                    _ => return Err(Error::msg(format!("Unexpected token {:?}", tok))),
+                    None => value,
+                };
+                Ok(Expression::Assign { name, value })
+            }
+            Expression::Index { left, index } => Ok(Expression::IndexAssign { left, index, operator, value }),
+            _ => unreachable!("the target was checked above"),
+        }
+    }
+
+    /// Parses `(<args>)` following an expression in call position
+    pub fn parse_call_expression(&mut self, function: Expression) -> Result<Expression> {
+        self.expect_peek(&Token::LParen)?;
+        let arguments = self.parse_call_arguments()?;
+        Ok(Expression::Call { function: Box::new(function), arguments })
+    }
+
+    /// Parses comma-separated arguments up to and including the closing `RParen`
+    pub fn parse_call_arguments(&mut self) -> Result<Vec<Expression>> {
+        self.parse_expression_list(Token::RParen)
+    }
+
+    /// Parses `[index]`, or a slice `[start:end]` where either bound may be omitted
+    pub fn parse_index_expression(&mut self, left: Expression) -> Result<Expression> {
+        self.expect_peek(&Token::LBracket)?;
+        if self.peek_is(&Token::Colon) {
+            return self.parse_slice(left, None);
+        }
+        let index = Box::new(self.parse_expression(Precedence::Lowest)?);
+        if self.peek_is(&Token::Colon) {
+            return self.parse_slice(left, Some(index));
+        }
+        self.expect_peek(&Token::RBracket)?;
+        Ok(Expression::Index { left: Box::new(left), index })
+    }
+
+    /// Parses the `:end]` of a slice, with the colon as the next token
+    fn parse_slice(&mut self, left: Expression, start: Option<Box<Expression>>) -> Result<Expression> {
+        self.expect_peek(&Token::Colon)?;
+        let end = if self.peek_is(&Token::RBracket) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+        };
+        self.expect_peek(&Token::RBracket)?;
+        Ok(Expression::Slice { left: Box::new(left), start, end })
+    }
+
+    /// Parses the elements and closing `RBracket` following a `[` token
+    pub fn parse_array_literal(&mut self) -> Result<Expression> {
+        let elements = self.parse_expression_list(Token::RBracket)?;
+        Ok(Expression::Array(elements))
+    }
+
+    /// Parses the `key: value` pairs and closing `RBrace` following a `{` token
+    pub fn parse_hash_literal(&mut self) -> Result<Expression> {
+        let mut pairs = Vec::new();
+        if self.peek_is(&Token::RBrace) {
+            self.next_token();
+            return Ok(Expression::Hash(pairs));
+        }
+        loop {
+            let key = self.parse_expression(Precedence::Lowest)?;
+            self.expect_peek(&Token::Colon)?;
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+            match self.next_token() {
+                // a trailing comma is allowed before the closing brace
+                Some(Token::Comma) if self.peek_is(&Token::RBrace) => {
+                    self.next_token();
+                    break;
+                }
+                Some(Token::Comma) => continue,
+                Some(Token::RBrace) => break,
+                Some(tok) => return Err(self.unexpected(format!("Expected Comma or RBrace, got {:?}", tok))),
+                None => return Err(self.unexpected_at_peek("Expected Comma or RBrace, got EOF".to_string())),
+            }
+        }
+        Ok(Expression::Hash(pairs))
+    }
+
+    /// Parses comma-separated expressions up to and including the `end` token,
+    /// allowing a trailing comma after the last expression
+    pub fn parse_expression_list(&mut self, end: Token) -> Result<Vec<Expression>> {
+        let mut list = Vec::new();
+        if self.peek_is(&end) {
+            self.next_token();
+            return Ok(list);
+        }
+        loop {
+            list.push(self.parse_expression(Precedence::Lowest)?);
+            match self.next_token() {
+                Some(Token::Comma) if self.peek_is(&end) => {
+                    self.next_token();
+                    break;
                 }
+                Some(Token::Comma) => continue,
+                Some(tok) if tok == end => break,
+                Some(tok) => return Err(self.unexpected(format!("Expected Comma or {:?}, got {:?}", end, tok))),
+                None => return Err(self.unexpected_at_peek(format!("Expected Comma or {:?}, got EOF", end))),
+            }
+        }
+        Ok(list)
+    }
+    
+    pub fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression> {
+        let tok = self.expect_token()?;
+        let mut expr = match tok {
+            Token::Ident(ident) => Expression::Identifier(Identifier { value: ident }),
+            Token::Int(int) => Expression::IntegerLiteral(int),
+            Token::IntTooLarge(literal) => return Err(self.invalid(format!("Integer literal too large: {}", literal))),
+            Token::IllegalEscape(c) => return Err(self.invalid(format!("Unknown escape sequence \\{}", c))),
+            Token::Float(float) => Expression::FloatLiteral(float),
+            Token::Str(string) => Expression::StringLiteral(string),
+            Token::Char(c) => Expression::CharLiteral(c),
+            Token::Bool(boolean) => Expression::Boolean(boolean),
+            Token::Null => Expression::Null,
+            Token::Bang | Token::Minus => {
+                let operator = tok;
+                let right = Box::new(self.parse_expression(Precedence::Prefix)?);
+                Expression::Prefix { operator, right }
             },
-            _ => return Err(Error::msg("Unexpected EOF")),
+            Token::LParen => {
+                let expr = self.parse_expression(Precedence::Lowest)?;
+                self.expect_peek(&Token::RParen)?;
+                expr
+            },
+            Token::If => self.parse_if_expression()?,
+            Token::Function => self.parse_function_literal()?,
+            Token::Macro => self.parse_macro_literal()?,
+            Token::LBracket => self.parse_array_literal()?,
+            Token::LBrace => self.parse_hash_literal()?,
+            _ => return Err(self.unexpected(format!("Unexpected token {:?}", tok))),
         };
         
-        while let Some(tok) = self.lexer.peek() {
-            let peeked_precedence = Precedence::from_token(tok);
-            if *tok != Token::Semicolon && precedence < peeked_precedence {
-                expr = self.parse_infix_expression(expr)?;
+        while let Some(tok) = self.peek_token() {
+            if precedence < Precedence::from_token(tok) {
+                expr = match tok {
+                    Token::LParen => self.parse_call_expression(expr)?,
+                    Token::LBracket => self.parse_index_expression(expr)?,
+                    Token::Assign | Token::PlusEq | Token::MinusEq | Token::AsteriskEq | Token::SlashEq => {
+                        self.parse_assign_expression(expr)?
+                    },
+                    _ => self.parse_infix_expression(expr)?,
+                };
             } else {
                 break
             }
@@ -165,36 +616,42 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     
-    pub fn parse_program(&mut self) -> Result<Program> {
-        let mut program = Program::new();
-        let mut errors: Vec<String> = Vec::new();
-        
-        while let Some(tok) = self.lexer.peek() {
+    /// Skips past the rest of a broken statement: up to and including the next
+    /// `Semicolon`, or up to the next `Let`/`Return`/`While`, whichever comes first
+    pub fn synchronize(&mut self) {
+        while let Some(tok) = self.peek_token() {
             match tok {
-                Token::Let => {
-                    self.lexer.next();
-                    match self.parse_let_statement() {
-                        Ok(statement) => program.add_statement(statement),
-                        Err(e) => errors.push(e.to_string()),
-                    }
-                },
-                Token::Return => {
-                    self.lexer.next();
-                    match self.parse_return_statement() {
-                        Ok(statement) => program.add_statement(statement),
-                        Err(e) => errors.push(e.to_string()),
-                    }
+                Token::Let | Token::Return | Token::While => return,
+                Token::Semicolon => {
+                    self.next_token();
+                    return;
                 },
                 _ => {
-                    match self.parse_expression_statement() {
-                        Ok(statement) => program.add_statement(statement),
-                        Err(e) => errors.push(e.to_string()),
-                    }
+                    self.next_token();
+                },
+            }
+        }
+    }
+
+    /// Parses statements until EOF, collecting every error rather than stopping
+    /// at the first. Each statement's span runs from its first token to its
+    /// last, including any `;`.
+    pub fn parse_program(&mut self) -> std::result::Result<Program, Vec<ParseError>> {
+        let mut program = Program::new();
+        let mut errors: Vec<ParseError> = Vec::new();
+        
+        while self.peek_token().is_some() {
+            let start = self.peeked_span.0;
+            match self.parse_statement() {
+                Ok(statement) => program.add_spanned_statement(statement, (start, self.span.1)),
+                Err(e) => {
+                    errors.push(e);
+                    self.synchronize();
                 },
             }
         }
         if !errors.is_empty() {
-            Err(Error::msg(format!("Parser error: {:?}", errors)))
+            Err(errors)
         } else {
             Ok(program)
         }
@@ -208,6 +665,57 @@ mod tests {
     use crate::ast::Statement;
     use super::*;
 
+    #[test]
+    fn test_statement_spans() {
+        let program = Parser::new(Lexer::new("let x = 5;")).parse_program().unwrap();
+        assert_eq!(program.span(0), Some((0, 10)));
+
+        let input = "  let x = 5;\n// note\nx + 1\nfn(a) {\n  a\n}(x);";
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        let sources: Vec<&str> = (0..program.statements.len())
+            .map(|i| program.span(i).unwrap())
+            .map(|(start, end)| &input[start..end])
+            .collect();
+        assert_eq!(sources, vec!["let x = 5;", "x + 1", "fn(a) {\n  a\n}(x);"]);
+    }
+
+    #[test]
+    fn test_truncated_input_is_unexpected_eof() {
+        let test_cases = vec![
+            "let x =",
+            "let x",
+            "fn(x) { x",
+            "add(1, 2",
+            "[1, 2,",
+            "{1: 2",
+            "if (x",
+            "1 +",
+            "-",
+        ];
+        for input in test_cases {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+            assert!(matches!(errors[..], [ParseError::UnexpectedEof { .. }]), "{}: {:?}", input, errors);
+        }
+    }
+
+    #[test]
+    fn test_wrong_input_is_not_unexpected_eof() {
+        let test_cases = vec![
+            ("let x 5;", "Expected Assign, got Int(5)"),
+            ("add(1 2)", "Expected Comma or RParen, got Int(2)"),
+            (")", "Unexpected token RParen"),
+        ];
+        for (input, expected) in test_cases {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+            match &errors[0] {
+                ParseError::UnexpectedToken { message, .. } => assert_eq!(message, expected, "{}", input),
+                error => panic!("Expected UnexpectedToken for {}, got {:?}", input, error),
+            }
+        }
+        let errors = Parser::new(Lexer::new("1 = 2")).parse_program().unwrap_err();
+        assert!(matches!(errors[0], ParseError::Invalid { .. }));
+    }
+
     #[test]
     fn test_print_program() {
         let input = "let x = 5; let y = 10; let foobar = 838383;";
@@ -240,6 +748,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_let_statement_values() {
+        let test_cases = vec![
+            ("let x = 5;", "x", "5"),
+            ("let y = true;", "y", "true"),
+            ("let foobar = y + 2 * x", "foobar", "(y + (2 * x))"),
+        ];
+        for (input, name, expected_value) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+
+            assert_eq!(program.statements.len(), 1);
+
+            match program.statements[0].clone() {
+                Statement::Let { ident, value } => {
+                    assert_eq!(ident.value, name);
+                    assert_eq!(value.to_string(), expected_value);
+                },
+                _ => panic!("Expected Let statement"),
+            }
+        }
+    }
+
     #[test]
     fn test_return_statements() {
         let input = "return 5; return 10; return 993322;";
@@ -249,9 +781,10 @@ mod tests {
 
         assert_eq!(program.statements.len(), 3);
 
-        for statement in program.statements {
+        let expected = ["5", "10", "993322"];
+        for (statement, expected_value) in program.statements.into_iter().zip(expected) {
             match statement {
-                Statement::Return { value: _ } => (),
+                Statement::Return { value } => assert_eq!(value.to_string(), expected_value),
                 _ => panic!("Expected Return statement"),
             }
         }
@@ -268,7 +801,7 @@ mod tests {
         assert_eq!(program.statements.len(), 1);
 
         match program.statements[0].clone() {
-            Statement::Expression { value } => {
+            Statement::Expression { value, .. } => {
                 match value {
                     Expression::Identifier(ident) => assert_eq!(ident.value, "foobar"),
                     _ => panic!("Expected Identifier expression"),
@@ -288,7 +821,7 @@ mod tests {
         assert_eq!(program.statements.len(), 1);
 
         match program.statements[0].clone() {
-            Statement::Expression { value } => {
+            Statement::Expression { value, .. } => {
                 match value {
                     Expression::IntegerLiteral(int) => assert_eq!(int, 5),
                     _ => panic!("Expected IntegerLiteral expression"),
@@ -298,6 +831,66 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_float_expression() {
+        let input = "2.5;";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+
+        match program.statements[0].clone() {
+            Statement::Expression { value, .. } => {
+                match value {
+                    Expression::FloatLiteral(float) => assert_eq!(float, 2.5),
+                    _ => panic!("Expected FloatLiteral expression"),
+                }
+            },
+            _ => panic!("Expected Expression statement"),
+        }
+    }
+    
+    #[test]
+    fn test_string_literal_expression() {
+        let input = r#""hello world";"#;
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+
+        match program.statements[0].clone() {
+            Statement::Expression { value, .. } => {
+                match value {
+                    Expression::StringLiteral(string) => assert_eq!(string, "hello world"),
+                    _ => panic!("Expected StringLiteral expression"),
+                }
+            },
+            _ => panic!("Expected Expression statement"),
+        }
+    }
+    
+    #[test]
+    fn test_char_literal_expression() {
+        let input = r"'\n';";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+
+        match program.statements[0].clone() {
+            Statement::Expression { value, .. } => {
+                match value {
+                    Expression::CharLiteral(c) => assert_eq!(c, '\n'),
+                    _ => panic!("Expected CharLiteral expression"),
+                }
+            },
+            _ => panic!("Expected Expression statement"),
+        }
+    }
+
     #[test]
     fn test_prefix_expression() {
         let test_cases = vec![
@@ -312,7 +905,7 @@ mod tests {
             assert_eq!(program.statements.len(), 1);
             
             match program.statements[0].clone() {
-                Statement::Expression { value } => {
+                Statement::Expression { value, .. } => {
                     match value {
                         Expression::Prefix { operator: op, right } => {
                             assert_eq!(op.to_string(), operator);
@@ -345,7 +938,7 @@ mod tests {
             assert_eq!(program.statements.len(), 1);
             
             match program.statements[0].clone() {
-                Statement::Expression { value } => {
+                Statement::Expression { value, .. } => {
                     match value {
                         Expression::Infix { left, operator: op, right } => {
                             assert_eq!(left.deref(), &Expression::IntegerLiteral(left_val));
@@ -363,31 +956,66 @@ mod tests {
     #[test]
     fn test_operator_precedence_parsing() {
         let test_cases = vec![
-            ("-a * b;", "((-a) * b)"),
-            ("!-a;", "(!(-a))"),
-            ("a + b + c;", "((a + b) + c)"),
-            ("a + b - c;", "((a + b) - c)"),
-            ("a * b * c;", "((a * b) * c)"),
-            ("a * b / c;", "((a * b) / c)"),
-            ("a + b / c;", "(a + (b / c))"),
-            ("a + b * c + d / e - f;", "(((a + (b * c)) + (d / e)) - f)"),
-            ("3 + 4; -5 * 5;", "(3 + 4)((-5) * 5)"),
-            ("5 > 4 == 3 < 4;", "((5 > 4) == (3 < 4))"),
-            ("5 < 4 != 3 > 4;", "((5 < 4) != (3 > 4))"),
-            ("3 + 4 * 5 == 3 * 1 + 4 * 5;", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+            ("-a * b;", "((-a) * b);"),
+            ("!-a;", "(!(-a));"),
+            ("a + b + c;", "((a + b) + c);"),
+            ("a + b - c;", "((a + b) - c);"),
+            ("a * b * c;", "((a * b) * c);"),
+            ("a * b / c;", "((a * b) / c);"),
+            ("a % b * c;", "((a % b) * c);"),
+            ("a + b % c;", "(a + (b % c));"),
+            ("a + b / c;", "(a + (b / c));"),
+            ("a + b * c + d / e - f;", "(((a + (b * c)) + (d / e)) - f);"),
+            ("3 + 4; -5 * 5;", "(3 + 4);\n((-5) * 5);"),
+            ("5 > 4 == 3 < 4;", "((5 > 4) == (3 < 4));"),
+            ("5 < 4 != 3 > 4;", "((5 < 4) != (3 > 4));"),
+            ("5 <= 4 == 3 >= 4;", "((5 <= 4) == (3 >= 4));"),
+            ("a + b >= c * d", "((a + b) >= (c * d))"),
+            ("2 ** 3 ** 2", "(2 ** (3 ** 2))"),
+            ("a ** b ** c ** d", "(a ** (b ** (c ** d)))"),
+            ("a - b - c - d", "(((a - b) - c) - d)"),
+            ("a / b / c", "((a / b) / c)"),
+            ("a * b ** c", "(a * (b ** c))"),
+            ("a ** b * c", "((a ** b) * c)"),
+            ("-a ** b", "(-(a ** b))"),
+            ("a ** -b", "(a ** (-b))"),
+            ("a ** f(b)[0]", "(a ** (f(b)[0]))"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c && d", "((a && b) || (c && d))"),
+            ("a == b && c < d", "((a == b) && (c < d))"),
+            ("3 + 4 * 5 == 3 * 1 + 4 * 5;", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)));"),
             ("!true", "(!true)"),
             ("!false", "(!false)"),
-            ("(5 + 5) * 2", "((5 + 5) * 2)")
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            ("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))"),
+            ("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g))"),
+            ("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d)"),
+            ("a * [1, 2][b]", "(a * ([1, 2][b]))"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))"),
         ];
         for (input, expected) in test_cases {
             let lexer = Lexer::new(input);
             let mut parser = Parser::new(lexer);
             let program = parser.parse_program().unwrap();
             
-            assert_eq!(format!("{}", program), expected);
+            assert_eq!(format!("{}", program), format!("{}\n", expected));
         }
     }
    
+    #[test]
+    fn test_precedence_levels() {
+        let mut level = Precedence::Lowest;
+        while level != Precedence::Index {
+            assert!(level < level.next());
+            assert_eq!(level.next().previous(), level);
+            level = level.next();
+        }
+        assert_eq!(Associativity::from_token(&Token::Pow), Associativity::Right);
+        assert_eq!(Associativity::from_token(&Token::Assign), Associativity::Right);
+        assert_eq!(Associativity::from_token(&Token::Minus), Associativity::Left);
+    }
+
     #[test]
     fn test_boolean_literal() {
 let test_cases = vec![
@@ -402,7 +1030,7 @@ let test_cases = vec![
             assert_eq!(program.statements.len(), 1);
             
             match program.statements[0].clone() {
-                Statement::Expression { value } => {
+                Statement::Expression { value, .. } => {
                     match value {
                         Expression::Boolean(boolean) => assert_eq!(boolean, expected),
                         _ => panic!("Expected Boolean expression"),
@@ -412,5 +1040,544 @@ let test_cases = vec![
             }
         }
     }
-    
-}
\ No newline at end of file
+
+    #[test]
+    fn test_null_literal() {
+        let mut parser = Parser::new(Lexer::new("let x = null; x == null"));
+        let program = parser.parse_program().unwrap();
+
+        match &program.statements[0] {
+            Statement::Let { value, .. } => assert_eq!(value, &Expression::Null),
+            _ => panic!("Expected Let statement"),
+        }
+        assert_eq!(program.to_string(), "let x = null;\n(x == null)\n");
+    }
+
+    #[test]
+    fn test_function_literal() {
+        let test_cases = vec![
+            ("fn() {};", vec![], 0),
+            ("fn(x) { x; };", vec!["x"], 1),
+            ("fn(x, y, z) { x + y; z };", vec!["x", "y", "z"], 2),
+        ];
+        for (input, expected_params, expected_len) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+
+            assert_eq!(program.statements.len(), 1);
+
+            match program.statements[0].clone() {
+                Statement::Expression { value: Expression::FunctionLiteral { parameters, body }, .. } => {
+                    let params: Vec<&str> = parameters.iter().map(|(p, _)| p.value.as_str()).collect();
+                    assert_eq!(params, expected_params);
+                    match body.deref() {
+                        Statement::Block { statements } => assert_eq!(statements.len(), expected_len),
+                        _ => panic!("Expected Block statement"),
+                    }
+                },
+                _ => panic!("Expected FunctionLiteral expression"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_function_literal_display() {
+        let input = "fn(x, y) { x + y; }";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(format!("{}", program), "fn(x, y) { (x + y); }\n");
+    }
+
+    #[test]
+    fn test_function_default_parameters() {
+        let input = "fn(x, y = 10, z = x * 2) { x + y + z }";
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+
+        match &program.statements[0] {
+            Statement::Expression { value: Expression::FunctionLiteral { parameters, .. }, .. } => {
+                let defaults: Vec<(&str, Option<String>)> = parameters
+                    .iter()
+                    .map(|(name, default)| (name.value.as_str(), default.as_ref().map(|d| d.to_string())))
+                    .collect();
+                assert_eq!(defaults, vec![("x", None), ("y", Some("10".to_string())), ("z", Some("(x * 2)".to_string()))]);
+            }
+            _ => panic!("Expected FunctionLiteral expression"),
+        }
+        assert_eq!(program.to_string(), "fn(x, y = 10, z = (x * 2)) { ((x + y) + z) }\n");
+    }
+
+    #[test]
+    fn test_default_parameter_errors() {
+        let test_cases = vec![
+            ("fn(x = 1, y) { x }", "Parameter y must have a default value"),
+            ("macro(x = 1) { x }", "Macro parameter x cannot have a default value"),
+        ];
+        for (input, expected) in test_cases {
+            let mut parser = Parser::new(Lexer::new(input));
+            let errors = parser.parse_program().unwrap_err();
+            assert_eq!(errors[0].message(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_macro_literal() {
+        let input = "macro(x, y) { x + y; }";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::Expression { value: Expression::MacroLiteral { parameters, body }, .. } => {
+                let names: Vec<&str> = parameters.iter().map(|p| p.value.as_str()).collect();
+                assert_eq!(names, vec!["x", "y"]);
+                assert_eq!(body.to_string(), "{ (x + y); }");
+            },
+            _ => panic!("Expected MacroLiteral expression"),
+        }
+    }
+
+    #[test]
+    fn test_call_expression() {
+        let test_cases = vec![
+            ("add();", vec![]),
+            ("add(1);", vec!["1"]),
+            ("add(1, 2 * 3, 4 + 5);", vec!["1", "(2 * 3)", "(4 + 5)"]),
+        ];
+        for (input, expected_args) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+
+            assert_eq!(program.statements.len(), 1);
+
+            match program.statements[0].clone() {
+                Statement::Expression { value: Expression::Call { function, arguments }, .. } => {
+                    assert_eq!(function.to_string(), "add");
+                    let args: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+                    assert_eq!(args, expected_args);
+                },
+                _ => panic!("Expected Call expression"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let input = "if (x < y) { let z = 1; x + z } else { y }";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+
+        match program.statements[0].clone() {
+            Statement::Expression { value: Expression::If { condition, consequence, alternative }, .. } => {
+                assert_eq!(condition.to_string(), "(x < y)");
+                match consequence.deref() {
+                    Statement::Block { statements } => assert_eq!(statements.len(), 2),
+                    _ => panic!("Expected Block statement"),
+                }
+                match alternative.as_deref() {
+                    Some(Statement::Block { statements }) => assert_eq!(statements.len(), 1),
+                    _ => panic!("Expected Block alternative"),
+                }
+            },
+            _ => panic!("Expected If expression"),
+        }
+    }
+
+    #[test]
+    fn test_else_if_expression() {
+        let input = "if (a) { 1 } else if (b) { 2 } else { 3 }";
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+        let alternative = match &program.statements[0] {
+            Statement::Expression { value: Expression::If { alternative: Some(alternative), .. }, .. } => alternative,
+            _ => panic!("Expected If expression with an alternative"),
+        };
+        match alternative.as_ref() {
+            Statement::Expression { value: Expression::If { condition, alternative, .. }, .. } => {
+                assert_eq!(condition.to_string(), "b");
+                assert!(matches!(alternative.as_deref(), Some(Statement::Block { .. })));
+            }
+            _ => panic!("Expected nested If alternative"),
+        }
+    }
+
+    #[test]
+    fn test_if_expression_display() {
+        let test_cases = vec![
+            ("if (x < y) { x } else { y }", "if (x < y) { x } else { y }"),
+            ("if (x) { let y = 1; y + 1 }", "if (x) { let y = 1; (y + 1) }"),
+            ("if (f(x)) {}", "if (f(x)) {}"),
+            ("if (a) { 1 } else if (b) { 2 } else { 3 }", "if (a) { 1 } else if (b) { 2 } else { 3 }"),
+        ];
+        for (input, expected) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            assert_eq!(program.to_string(), format!("{}\n", expected));
+
+            // the output parses back to the same program
+            let mut parser = Parser::new(Lexer::new(expected));
+            assert_eq!(parser.parse_program().unwrap().to_string(), program.to_string());
+        }
+    }
+
+    #[test]
+    fn test_if_expression_without_alternative() {
+        let input = "if (x) { x }; y";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+
+        match program.statements[0].clone() {
+            Statement::Expression { value: Expression::If { alternative, .. }, .. } => assert!(alternative.is_none()),
+            _ => panic!("Expected If expression"),
+        }
+    }
+
+    #[test]
+    fn test_while_statement() {
+        let input = "while (x < 10) { x; }";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::While { condition, body } => {
+                assert_eq!(condition.to_string(), "(x < 10)");
+                assert_eq!(body.to_string(), "{ x; }");
+            },
+            _ => panic!("Expected While statement"),
+        }
+        assert_eq!(program.to_string(), "while (x < 10) { x; }\n");
+    }
+
+    #[test]
+    fn test_assign_expression() {
+        let test_cases = vec![
+            ("x = 5;", "x = 5;"),
+            ("x = x + 1;", "x = (x + 1);"),
+            ("x = y = z;", "x = y = z;"),
+            ("x = a || b;", "x = (a || b);"),
+            ("x += 1;", "x = (x + 1);"),
+            ("x -= y * 2;", "x = (x - (y * 2));"),
+            ("x *= 3;", "x = (x * 3);"),
+            ("x /= 2;", "x = (x / 2);"),
+        ];
+        for (input, expected) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+
+            assert_eq!(program.statements.len(), 1);
+            match &program.statements[0] {
+                Statement::Expression { value: Expression::Assign { name, .. }, .. } => assert_eq!(name.value, input[..1]),
+                _ => panic!("Expected Assign expression"),
+            }
+            assert_eq!(program.to_string(), format!("{}\n", expected));
+        }
+    }
+
+    #[test]
+    fn test_chained_assign_is_right_associative() {
+        let program = Parser::new(Lexer::new("a = b[0] = c;")).parse_program().unwrap();
+        match &program.statements[0] {
+            Statement::Expression { value: Expression::Assign { name, value }, .. } => {
+                assert_eq!(name.value, "a");
+                match value.as_ref() {
+                    Expression::IndexAssign { left, index, value, .. } => {
+                        assert_eq!((left.to_string(), index.to_string(), value.to_string()), ("b".into(), "0".into(), "c".into()));
+                    }
+                    other => panic!("Expected IndexAssign, got {:?}", other),
+                }
+            }
+            other => panic!("Expected Assign expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_index_assign_expression() {
+        let test_cases = vec![
+            ("a[0] = 1;", "a[0] = 1;"),
+            ("h[\"k\"] = x + 1;", "h[k] = (x + 1);"),
+            ("m[i][j] = 0;", "(m[i])[j] = 0;"),
+            ("a[i] += 2;", "a[i] += 2;"),
+            ("a[i] *= b[j] = 3;", "a[i] *= b[j] = 3;"),
+        ];
+        for (input, expected) in test_cases {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            assert_eq!(program.to_string(), format!("{}\n", expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        for input in ["5 = x;", "1 + 2 = 3;", "f() = 1;", "a[1:2] = b;"] {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+            assert_eq!(errors[0].message(), "invalid assignment target", "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_array_literal() {
+        let test_cases = vec![
+            ("[]", vec![]),
+            ("[1]", vec!["1"]),
+            ("[1, 2 * 2, 3 + 3]", vec!["1", "(2 * 2)", "(3 + 3)"]),
+            ("[[1], fn(x) { x }]", vec!["[1]", "fn(x) { x }"]),
+        ];
+        for (input, expected) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+
+            assert_eq!(program.statements.len(), 1);
+
+            match program.statements[0].clone() {
+                Statement::Expression { value: Expression::Array(elements), .. } => {
+                    let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                    assert_eq!(elements, expected);
+                },
+                _ => panic!("Expected Array expression"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_array_literal_display() {
+        let lexer = Lexer::new("[1, 2 * 2, 3 + 3]");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(format!("{}", program), "[1, (2 * 2), (3 + 3)]\n");
+    }
+
+    #[test]
+    fn test_slice_expression() {
+        let test_cases = vec![
+            ("s[1:3]", "(s[1:3])"),
+            ("s[:n + 1]", "(s[:(n + 1)])"),
+            ("s[2:]", "(s[2:])"),
+            ("s[:]", "(s[:])"),
+            ("a * s[1:][0]", "(a * ((s[1:])[0]))"),
+        ];
+        for (input, expected) in test_cases {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program().unwrap();
+            assert_eq!(program.to_string(), format!("{}\n", expected), "{}", input);
+        }
+
+        let mut parser = Parser::new(Lexer::new("s[1:2:3]"));
+        let errors = parser.parse_program().unwrap_err();
+        assert_eq!(errors[0].message(), "Expected RBracket, got Colon");
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let test_cases = vec![
+            ("add(1, 2,)", "add(1, 2)"),
+            ("add(1,)", "add(1)"),
+            ("[1, 2,]", "[1, 2]"),
+            ("[\n  1,\n  2,\n]", "[1, 2]"),
+            (r#"{"a": 1,}"#, "{a: 1}"),
+            (r#"{"a": 1, "b": 2,}"#, "{a: 1, b: 2}"),
+        ];
+        for (input, expected) in test_cases {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program().unwrap();
+            assert_eq!(program.to_string(), format!("{}\n", expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_malformed_commas() {
+        let test_cases = vec![
+            ("[,]", "Unexpected token Comma"),
+            ("add(,)", "Unexpected token Comma"),
+            ("{,}", "Unexpected token Comma"),
+            ("[1,,]", "Unexpected token Comma"),
+            ("add(1,, 2)", "Unexpected token Comma"),
+            (r#"{"a": 1,,}"#, "Unexpected token Comma"),
+        ];
+        for (input, expected) in test_cases {
+            let mut parser = Parser::new(Lexer::new(input));
+            let errors = parser.parse_program().unwrap_err();
+            assert_eq!(errors[0].message(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_index_expression() {
+        let lexer = Lexer::new("myArray[1 + 1]");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(program.statements.len(), 1);
+
+        match program.statements[0].clone() {
+            Statement::Expression { value: Expression::Index { left, index }, .. } => {
+                assert_eq!(left.to_string(), "myArray");
+                assert_eq!(index.to_string(), "(1 + 1)");
+            },
+            _ => panic!("Expected Index expression"),
+        }
+    }
+
+    #[test]
+    fn test_hash_literal() {
+        let test_cases = vec![
+            ("{}", vec![]),
+            (r#"{"one": 1, "two": 2, "three": 3}"#, vec![("one", "1"), ("two", "2"), ("three", "3")]),
+            (r#"{"one": 0 + 1, true: 10 - 8, 3: 15 / 5}"#, vec![("one", "(0 + 1)"), ("true", "(10 - 8)"), ("3", "(15 / 5)")]),
+        ];
+        for (input, expected) in test_cases {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+
+            assert_eq!(program.statements.len(), 1);
+
+            match program.statements[0].clone() {
+                Statement::Expression { value: Expression::Hash(pairs), .. } => {
+                    let pairs: Vec<(String, String)> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+                    let expected: Vec<(String, String)> = expected.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+                    assert_eq!(pairs, expected);
+                },
+                _ => panic!("Expected Hash expression"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_statement() {
+        let test_cases = vec![
+            ("{ let x = 5; x + 1 }", "{ let x = 5; (x + 1) }"),
+            ("{ x }", "{ x }"),
+            ("{ return 1; }", "{ return 1; }"),
+            ("{ { 1 } }", "{ { 1 } }"),
+            ("{ {\"a\": 1} }", "{ {a: 1} }"),
+            ("{ f(x); y };", "{ f(x); y }"),
+        ];
+        for (input, expected) in test_cases {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            assert_eq!(program.statements.len(), 1, "{}", input);
+            assert!(matches!(program.statements[0], Statement::Block { .. }), "{}", input);
+            assert_eq!(program.statements[0].to_string(), expected, "{}", input);
+        }
+
+        // a leading `{` is still a hash when empty or when its first key is a
+        // single literal or name followed by `:`
+        for input in ["{}", "{\"a\": 1}", "{x: {}}", "{1: 2, 3: 4}", "{true: 1}"] {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            assert!(matches!(program.statements[0], Statement::Expression { value: Expression::Hash(_), .. }), "{}", input);
+        }
+        let program = Parser::new(Lexer::new("{ 1 } 2")).parse_program().unwrap();
+        assert_eq!(program.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_deeply_nested_blocks_parse_in_linear_time() {
+        let depth = 40;
+        let input = format!("{}1{}", "{ fn() { ".repeat(depth), " } }".repeat(depth));
+        let program = Parser::new(Lexer::new(&input)).parse_program().unwrap();
+        assert_eq!(program.statements.len(), 1);
+        assert!(matches!(program.statements[0], Statement::Block { .. }));
+    }
+
+    #[test]
+    fn test_expect_peek() {
+        let mut parser = Parser::new(Lexer::new("( x"));
+
+        assert!(parser.peek_is(&Token::LParen));
+        assert!(parser.expect_peek(&Token::LParen).is_ok());
+        let error = parser.expect_peek(&Token::RParen).unwrap_err();
+        assert_eq!(error.message(), r#"Expected RParen, got Ident("x")"#);
+        assert!(parser.peek_is(&Token::Ident("x".to_string())));
+    }
+
+    #[test]
+    fn test_parse_errors_report_positions() {
+        let input = "let x 5;\nlet y 10;";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let errors = parser.parse_program().unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message(), "Expected Assign, got Int(5)");
+        assert_eq!(errors[0].position(), Position { line: 1, column: 7 });
+        assert_eq!(errors[1].message(), "Expected Assign, got Int(10)");
+        assert_eq!(errors[1].position(), Position { line: 2, column: 7 });
+    }
+
+    #[test]
+    fn test_integer_literal_too_large() {
+        let lexer = Lexer::new("let x = 99999999999999999999;");
+        let mut parser = Parser::new(lexer);
+        let errors = parser.parse_program().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "Integer literal too large: 99999999999999999999");
+        assert_eq!(errors[0].position(), Position { line: 1, column: 9 });
+    }
+
+    #[test]
+    fn test_unknown_escape_sequence() {
+        for (input, expected) in [(r#"let s = "a\qb";"#, r"Unknown escape sequence \q"), (r"let c = '\z';", r"Unknown escape sequence \z")] {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+            assert_eq!(errors[0].message(), expected, "{}", input);
+            assert_eq!(errors[0].position(), Position { line: 1, column: 9 }, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_expected_identifier_messages() {
+        let test_cases = vec![
+            ("let if = 1;", "Expected identifier, got keyword `if`; keywords can't be used as names"),
+            ("let fn = 5;", "Expected identifier, got keyword `fn`; keywords can't be used as names"),
+            ("let true = 5;", "Expected identifier, got keyword `true`; keywords can't be used as names"),
+            ("fn(x, while) { x }", "Expected identifier, got keyword `while`; keywords can't be used as names"),
+            ("let = 5;", "Expected identifier, got Assign"),
+            ("let 7 = y;", "Expected identifier, got Int(7)"),
+            ("let", "Expected identifier, got EOF"),
+        ];
+        for (input, expected) in test_cases {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+            assert_eq!(errors[0].message(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_synchronize_after_parse_error() {
+        let lexer = Lexer::new("let = 5; let x = 10;");
+        let mut parser = Parser::new(lexer);
+
+        assert!(parser.parse_statement().is_err());
+        parser.synchronize();
+        let statement = parser.parse_statement().unwrap();
+        assert_eq!(statement.to_string(), "let x = 10;");
+        assert_eq!(parser.peek_token(), None);
+    }
+
+    #[test]
+    fn test_parse_errors_do_not_cascade() {
+        let lexer = Lexer::new("let = 5; let x = 10; let 7 = y; return x");
+        let mut parser = Parser::new(lexer);
+        let errors = parser.parse_program().unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].position(), Position { line: 1, column: 5 });
+        assert_eq!(errors[1].position(), Position { line: 1, column: 26 });
+    }
+}
@@ -0,0 +1,466 @@
+use crate::ast::{assignment_operator, Expression, Program, Statement};
+use crate::lexer::{quote, quote_char};
+use crate::parser::{Associativity, Precedence};
+use crate::token::Token;
+
+const INDENT: &str = "    ";
+const TREE_INDENT: &str = "  ";
+
+/// Renders a program as indented source with only the parentheses that
+/// precedence requires
+pub fn to_source(program: &Program) -> String {
+    let mut printer = Printer::default();
+    for statement in &program.statements {
+        printer.statement(statement);
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+/// Renders a program as a tree of its nodes for debugging, one node per line
+/// with its children indented beneath it:
+///
+/// ```text
+/// Program
+///   Let x
+///     Infix +
+///       Integer 1
+///       Integer 2
+/// ```
+pub fn print_tree(program: &Program) -> String {
+    let mut printer = TreePrinter::default();
+    printer.node("Program", |printer| {
+        for statement in &program.statements {
+            printer.statement(statement);
+        }
+    });
+    printer.out
+}
+
+#[derive(Default)]
+struct TreePrinter {
+    out: String,
+    depth: usize,
+}
+
+impl TreePrinter {
+    /// Writes a line for a node, then its children one level deeper
+    fn node(&mut self, label: &str, children: impl FnOnce(&mut Self)) {
+        for _ in 0..self.depth {
+            self.out.push_str(TREE_INDENT);
+        }
+        self.out.push_str(label);
+        self.out.push('\n');
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn leaf(&mut self, label: &str) {
+        self.node(label, |_| ());
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { ident, value } => self.node(&format!("Let {}", ident.value), |p| p.expression(value)),
+            Statement::Return { value } => self.node("Return", |p| p.expression(value)),
+            Statement::Expression { value, .. } => self.node("ExpressionStatement", |p| p.expression(value)),
+            Statement::Block { statements } => self.node("Block", |p| statements.iter().for_each(|s| p.statement(s))),
+            Statement::While { condition, body } => self.node("While", |p| {
+                p.expression(condition);
+                p.statement(body);
+            }),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(ident) => self.leaf(&format!("Identifier {}", ident.value)),
+            Expression::IntegerLiteral(value) => self.leaf(&format!("Integer {}", value)),
+            Expression::FloatLiteral(value) => self.leaf(&format!("Float {:?}", value)),
+            Expression::StringLiteral(value) => self.leaf(&format!("String {}", quote(value))),
+            Expression::CharLiteral(value) => self.leaf(&format!("Char {}", quote_char(*value))),
+            Expression::Boolean(value) => self.leaf(&format!("Boolean {}", value)),
+            Expression::Null => self.leaf("Null"),
+            Expression::Array(elements) => self.node("Array", |p| elements.iter().for_each(|e| p.expression(e))),
+            Expression::Hash(pairs) => self.node("Hash", |p| {
+                for (key, value) in pairs {
+                    p.node("Pair", |p| {
+                        p.expression(key);
+                        p.expression(value);
+                    });
+                }
+            }),
+            Expression::Prefix { operator, right } => self.node(&format!("Prefix {}", operator), |p| p.expression(right)),
+            Expression::Infix { left, operator, right } => self.node(&format!("Infix {}", operator), |p| {
+                p.expression(left);
+                p.expression(right);
+            }),
+            Expression::If { condition, consequence, alternative } => self.node("If", |p| {
+                p.expression(condition);
+                p.statement(consequence);
+                if let Some(alternative) = alternative {
+                    p.node("Else", |p| p.statement(alternative));
+                }
+            }),
+            Expression::FunctionLiteral { parameters, body } => self.node("Function", |p| {
+                for (name, default) in parameters {
+                    p.node(&format!("Parameter {}", name.value), |p| {
+                        if let Some(default) = default {
+                            p.expression(default);
+                        }
+                    });
+                }
+                p.statement(body);
+            }),
+            Expression::MacroLiteral { parameters, body } => self.node("Macro", |p| {
+                for name in parameters {
+                    p.leaf(&format!("Parameter {}", name.value));
+                }
+                p.statement(body);
+            }),
+            Expression::Call { function, arguments } => self.node("Call", |p| {
+                p.expression(function);
+                arguments.iter().for_each(|a| p.expression(a));
+            }),
+            Expression::Index { left, index } => self.node("Index", |p| {
+                p.expression(left);
+                p.expression(index);
+            }),
+            Expression::Slice { left, start, end } => self.node("Slice", |p| {
+                p.expression(left);
+                if let Some(start) = start {
+                    p.node("Start", |p| p.expression(start));
+                }
+                if let Some(end) = end {
+                    p.node("End", |p| p.expression(end));
+                }
+            }),
+            Expression::Assign { name, value } => self.node(&format!("Assign {}", name.value), |p| p.expression(value)),
+            Expression::IndexAssign { left, index, operator, value } => {
+                self.node(&format!("IndexAssign {}", assignment_operator(operator.as_ref())), |p| {
+                    p.expression(left);
+                    p.expression(index);
+                    p.expression(value);
+                })
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { ident, value } => {
+                self.out.push_str(&format!("{} {} {} ", Token::Let, ident.value, Token::Assign));
+                self.expression(value, Precedence::Lowest);
+                self.out.push(';');
+            }
+            Statement::Return { value } => {
+                self.out.push_str(&format!("{} ", Token::Return));
+                self.expression(value, Precedence::Lowest);
+                self.out.push(';');
+            }
+            Statement::Expression { value, semicolon } => {
+                self.expression(value, Precedence::Lowest);
+                if *semicolon {
+                    self.out.push(';');
+                }
+            }
+            Statement::Block { statements } => self.block(statements),
+            Statement::While { condition, body } => {
+                self.out.push_str(&format!("{} (", Token::While));
+                self.expression(condition, Precedence::Lowest);
+                self.out.push_str(") ");
+                self.statement(body);
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        if statements.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.depth += 1;
+        for statement in statements {
+            self.indent();
+            self.statement(statement);
+            self.out.push('\n');
+        }
+        self.depth -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    /// Writes `expression`, wrapping it in parentheses if it binds more
+    /// loosely than `context`, the precedence of the position it appears in
+    fn expression(&mut self, expression: &Expression, context: Precedence) {
+        let precedence = precedence_of(expression);
+        let parenthesize = precedence < context;
+        if parenthesize {
+            self.out.push('(');
+        }
+        match expression {
+            Expression::Identifier(ident) => self.out.push_str(&ident.value),
+            Expression::IntegerLiteral(value) => self.out.push_str(&value.to_string()),
+            Expression::FloatLiteral(value) => self.out.push_str(&format!("{:?}", value)),
+            Expression::StringLiteral(value) => self.out.push_str(&quote(value)),
+            Expression::CharLiteral(value) => self.out.push_str(&quote_char(*value)),
+            Expression::Boolean(value) => self.out.push_str(&value.to_string()),
+            Expression::Null => self.out.push_str(&Token::Null.to_string()),
+            Expression::Array(elements) => {
+                self.out.push('[');
+                self.list(elements);
+                self.out.push(']');
+            }
+            Expression::Hash(pairs) => {
+                self.out.push('{');
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expression(key, Precedence::Lowest);
+                    self.out.push_str(": ");
+                    self.expression(value, Precedence::Lowest);
+                }
+                self.out.push('}');
+            }
+            Expression::Prefix { operator, right } => {
+                self.out.push_str(&operator.to_string());
+                self.expression(right, Precedence::Prefix);
+            }
+            Expression::Infix { left, operator, right } => {
+                // an operand of equal precedence only needs parentheses on the
+                // side the operator doesn't group towards
+                let (left_context, right_context) = match Associativity::from_token(operator) {
+                    Associativity::Left => (precedence, precedence.next()),
+                    Associativity::Right => (precedence.next(), precedence),
+                };
+                self.expression(left, left_context);
+                self.out.push_str(&format!(" {} ", operator));
+                self.expression(right, right_context);
+            }
+            Expression::If { condition, consequence, alternative } => {
+                self.out.push_str(&format!("{} (", Token::If));
+                self.expression(condition, Precedence::Lowest);
+                self.out.push_str(") ");
+                self.statement(consequence);
+                if let Some(alternative) = alternative {
+                    self.out.push_str(&format!(" {} ", Token::Else));
+                    self.statement(alternative);
+                }
+            }
+            Expression::FunctionLiteral { parameters, body } => {
+                self.out.push_str(&format!("{}(", Token::Function));
+                for (i, (name, default)) in parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(&name.value);
+                    if let Some(default) = default {
+                        self.out.push_str(&format!(" {} ", Token::Assign));
+                        self.expression(default, Precedence::Lowest);
+                    }
+                }
+                self.out.push_str(") ");
+                self.statement(body);
+            }
+            Expression::MacroLiteral { parameters, body } => {
+                let parameters: Vec<&str> = parameters.iter().map(|p| p.value.as_str()).collect();
+                self.out.push_str(&format!("{}({}) ", Token::Macro, parameters.join(", ")));
+                self.statement(body);
+            }
+            Expression::Call { function, arguments } => {
+                self.expression(function, Precedence::Call);
+                self.out.push('(');
+                self.list(arguments);
+                self.out.push(')');
+            }
+            Expression::Index { left, index } => {
+                self.expression(left, Precedence::Index);
+                self.out.push('[');
+                self.expression(index, Precedence::Lowest);
+                self.out.push(']');
+            }
+            Expression::Slice { left, start, end } => {
+                self.expression(left, Precedence::Index);
+                self.out.push('[');
+                if let Some(start) = start {
+                    self.expression(start, Precedence::Lowest);
+                }
+                self.out.push(':');
+                if let Some(end) = end {
+                    self.expression(end, Precedence::Lowest);
+                }
+                self.out.push(']');
+            }
+            Expression::Assign { name, value } => {
+                self.out.push_str(&format!("{} {} ", name.value, Token::Assign));
+                self.expression(value, Precedence::Lowest);
+            }
+            Expression::IndexAssign { left, index, operator, value } => {
+                self.expression(left, Precedence::Index);
+                self.out.push('[');
+                self.expression(index, Precedence::Lowest);
+                self.out.push_str(&format!("] {} ", assignment_operator(operator.as_ref())));
+                self.expression(value, Precedence::Lowest);
+            }
+        }
+        if parenthesize {
+            self.out.push(')');
+        }
+    }
+
+    fn list(&mut self, expressions: &[Expression]) {
+        for (i, expression) in expressions.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expression(expression, Precedence::Lowest);
+        }
+    }
+}
+
+/// How tightly an expression binds; anything that is not an operator is atomic
+fn precedence_of(expression: &Expression) -> Precedence {
+    match expression {
+        Expression::Infix { operator, .. } => Precedence::from_token(operator),
+        Expression::Assign { .. } | Expression::IndexAssign { .. } => Precedence::Assign,
+        Expression::Prefix { .. } => Precedence::Prefix,
+        Expression::Call { .. } => Precedence::Call,
+        _ => Precedence::Index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{print_tree, to_source};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn format(input: &str) -> String {
+        let mut parser = Parser::new(Lexer::new(input));
+        to_source(&parser.parse_program().unwrap())
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        let test_cases = vec![
+            ("(a + b) * c;", "(a + b) * c;\n"),
+            ("a + (b * c);", "a + b * c;\n"),
+            ("(a - b) - c;", "a - b - c;\n"),
+            ("a - (b - c);", "a - (b - c);\n"),
+            ("a ** (b ** c);", "a ** b ** c;\n"),
+            ("(a ** b) ** c;", "(a ** b) ** c;\n"),
+            ("(-a) ** b;", "(-a) ** b;\n"),
+            ("-(a + b);", "-(a + b);\n"),
+            ("!(-a);", "!-a;\n"),
+            ("(a + b)[0];", "(a + b)[0];\n"),
+            ("s[1:n - 1][:2][i:];", "s[1:n - 1][:2][i:];\n"),
+            ("if (x) { a; b }", "if (x) {\n    a;\n    b\n}\n"),
+            ("f(x)", "f(x)\n"),
+            ("add(a * (b + c), [1, 2][0]);", "add(a * (b + c), [1, 2][0]);\n"),
+            ("x = y = (a || b) && c;", "x = y = (a || b) && c;\n"),
+            ("(a + b)[i] = m[i][j] = 0;", "(a + b)[i] = m[i][j] = 0;\n"),
+            ("a[i] -= (x = 1);", "a[i] -= x = 1;\n"),
+            ("let s = \"say \\\"hi\\\"\\n\";", "let s = \"say \\\"hi\\\"\\n\";\n"),
+            ("['a', '\\'', '\"'];", "['a', '\\'', '\"'];\n"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(format(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_nested_if() {
+        let input = "if (a > b) { if (a > 10) { return a; } else { b } } else if (b > 0) { b } else { 0 }";
+        let expected = "\
+if (a > b) {
+    if (a > 10) {
+        return a;
+    } else {
+        b
+    }
+} else if (b > 0) {
+    b
+} else {
+    0
+}
+";
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_function_literal() {
+        let input = "let add = fn(x, y) { let sum = (x + y); while (sum > 10) { sum = sum - 1; } sum }; add(1, 2);";
+        let expected = "\
+let add = fn(x, y) {
+    let sum = x + y;
+    while (sum > 10) {
+        sum = sum - 1;
+    }
+    sum
+};
+add(1, 2);
+";
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_formatted_source_reparses() {
+        let input = "let f = fn(n) { if (n < 2) { n } else { f(n - 1) + f(n - (1 + 1)) } }; f(-(3 * 2));";
+        let formatted = format(input);
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_print_tree() {
+        let program = Parser::new(Lexer::new("let x = 1 + 2;")).parse_program().unwrap();
+        assert_eq!(print_tree(&program), "Program\n  Let x\n    Infix +\n      Integer 1\n      Integer 2\n");
+
+        let input = "if (a[1:]) { f(\"s\", x = 2) } else { fn(y, z = 0) { -y } }";
+        let expected = "\
+Program
+  ExpressionStatement
+    If
+      Slice
+        Identifier a
+        Start
+          Integer 1
+      Block
+        ExpressionStatement
+          Call
+            Identifier f
+            String \"s\"
+            Assign x
+              Integer 2
+      Else
+        Block
+          ExpressionStatement
+            Function
+              Parameter y
+              Parameter z
+                Integer 0
+              Block
+                ExpressionStatement
+                  Prefix -
+                    Identifier y
+";
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        assert_eq!(print_tree(&program), expected);
+    }
+}
@@ -1,19 +1,514 @@
-use std::io::Write;
+use std::cell::RefCell;
+use std::io;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+use crate::ast::{Program, Span};
+use crate::builtins;
+use crate::compiler::Compiler;
+use crate::diagnostic::Diagnostic;
+use crate::environment::Environment;
+use crate::evaluator::Evaluator;
 use crate::lexer::Lexer;
+use crate::macro_expansion;
+use crate::object::Object;
+use crate::parser::{ParseError, Parser};
+use crate::pretty;
+use crate::vm::Vm;
+use crate::{timed, Timings};
 
 const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
 
-pub fn start() {
+/// How the REPL treats each line of input
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplMode {
+    /// Evaluate the line and print its value
+    #[default]
+    Eval,
+    /// Print each token the lexer produces
+    Tokens,
+    /// Print the parsed program as a tree of its nodes
+    Ast,
+    /// Compile the line to bytecode and run it on the VM
+    Vm,
+}
+
+impl ReplMode {
+    /// The mode selected by a command-line flag such as `--tokens`
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "--eval" => Some(ReplMode::Eval),
+            "--tokens" => Some(ReplMode::Tokens),
+            "--ast" => Some(ReplMode::Ast),
+            _ => None,
+        }
+    }
+}
+
+/// What the REPL should do with the line it just read
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Quit,
+    Eval,
+    SetMode(ReplMode),
+    /// List the session's bindings, and the built-ins too if `all` is set
+    ShowEnv { all: bool },
+    /// Evaluate the script at this path into the session
+    Load(String),
+    /// Turn reporting how long each line or loaded script took to parse and
+    /// run on or off
+    SetTiming(bool),
+}
+
+/// Runs the REPL until input ends, starting in `mode` and reporting timings
+/// to stderr if `timing` is set
+pub fn start(mut mode: ReplMode, mut timing: bool) {
     let mut input = String::new();
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout();
+    // one environment for the whole session so bindings survive between lines
+    let env = Rc::new(RefCell::new(Environment::new()));
     loop {
         print!("{}", PROMPT);
-        std::io::stdout().flush().unwrap();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let l = Lexer::new(&input);
-        
-        for tok in l {
-            println!("{:?}", tok);
+        stdout.flush().unwrap();
+        match read_command(&mut stdin, &mut input).unwrap() {
+            Command::Quit => break,
+            Command::Eval => {
+                // keep reading while the parser runs out of input, until input
+                // itself runs out or a blank line asks for the errors as they are
+                while is_incomplete(&input) {
+                    print!("{}", CONTINUATION_PROMPT);
+                    stdout.flush().unwrap();
+                    let start = input.len();
+                    if stdin.read_line(&mut input).unwrap() == 0 || input[start..].trim().is_empty() {
+                        break;
+                    }
+                }
+                let timings = handle_line_timed(mode, &input, &env, &mut stdout).unwrap();
+                report_timings(timing, Some(timings));
+            }
+            Command::SetMode(new_mode) => mode = new_mode,
+            Command::SetTiming(on) => timing = on,
+            Command::ShowEnv { all } => write_env(&env.borrow(), all, &mut stdout).unwrap(),
+            Command::Load(path) => report_timings(timing, load_file_timed(&path, &env, &mut stdout).unwrap()),
         }
         input.clear();
     }
 }
+
+/// Writes `timings` to stderr, as the script runner does, if timing is on
+fn report_timings(timing: bool, timings: Option<Timings>) {
+    if let (true, Some(timings)) = (timing, timings) {
+        eprintln!("{}", timings);
+    }
+}
+
+/// Reads the next line into `buf`. End of input (a 0-byte read), `:quit`,
+/// and `exit` all end the session; `:eval`, `:tokens` and `:ast` switch modes,
+/// `:engine vm` / `:engine eval` pick what runs the code, `:env` or
+/// `:env all` list bindings, `:load <path>` runs a script, and `:time on` or
+/// `:time off` toggles timings.
+pub fn read_command(reader: &mut impl BufRead, buf: &mut String) -> io::Result<Command> {
+    if reader.read_line(buf)? == 0 {
+        return Ok(Command::Quit);
+    }
+    match buf.trim() {
+        ":quit" | "exit" => Ok(Command::Quit),
+        ":eval" => Ok(Command::SetMode(ReplMode::Eval)),
+        ":tokens" => Ok(Command::SetMode(ReplMode::Tokens)),
+        ":ast" => Ok(Command::SetMode(ReplMode::Ast)),
+        ":engine vm" => Ok(Command::SetMode(ReplMode::Vm)),
+        ":engine eval" => Ok(Command::SetMode(ReplMode::Eval)),
+        ":env" => Ok(Command::ShowEnv { all: false }),
+        ":env all" => Ok(Command::ShowEnv { all: true }),
+        ":time on" => Ok(Command::SetTiming(true)),
+        ":time off" => Ok(Command::SetTiming(false)),
+        line => match line.strip_prefix(":load ") {
+            Some(path) => Ok(Command::Load(path.trim().to_string())),
+            None => Ok(Command::Eval),
+        },
+    }
+}
+
+/// Whether `input` only fails to parse because it ends too soon, e.g. with a
+/// block still open, so that reading more lines could complete it
+pub fn is_incomplete(input: &str) -> bool {
+    match Parser::new(Lexer::new(input)).parse_program() {
+        Ok(_) => false,
+        Err(errors) => errors.iter().all(ParseError::is_eof),
+    }
+}
+
+/// Handles a line of input according to `mode`
+pub fn handle_line(mode: ReplMode, input: &str, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> io::Result<()> {
+    handle_line_timed(mode, input, env, out).map(|_| ())
+}
+
+/// Like [`handle_line`], also returning how long the line took. In `:tokens`
+/// and `:ast` mode, which don't run the line, it all counts as parsing.
+pub fn handle_line_timed(mode: ReplMode, input: &str, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> io::Result<Timings> {
+    let (result, parse_time) = match mode {
+        ReplMode::Eval => return eval_line_timed(input, env, out),
+        ReplMode::Vm => return vm_line_timed(input, out),
+        ReplMode::Tokens => timed(|| {
+            for tok in Lexer::new(input) {
+                writeln!(out, "{:?}", tok)?;
+            }
+            Ok(())
+        }),
+        ReplMode::Ast => timed(|| match Parser::new(Lexer::new(input)).parse_program() {
+            Ok(program) => write!(out, "{}", pretty::print_tree(&program)),
+            Err(errors) => write_parser_errors(input, &errors, out),
+        }),
+    };
+    result.map(|()| Timings { parse: parse_time, ..Timings::default() })
+}
+
+/// Parses and evaluates a single line of input against `env`, writing the
+/// result to `out`, or the errors rendered against the line
+pub fn eval_line(input: &str, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> io::Result<()> {
+    eval_line_timed(input, env, out).map(|_| ())
+}
+
+/// Like [`eval_line`], also returning how long parsing and evaluation took
+pub fn eval_line_timed(input: &str, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> io::Result<Timings> {
+    let (parsed, parse_time) = timed(|| Parser::new(Lexer::new(input)).parse_program());
+    let mut timings = Timings { parse: parse_time, ..Timings::default() };
+    match parsed {
+        Ok(program) => {
+            let ((result, span), eval_time) = timed(|| eval_program(program, env, out));
+            timings.eval = eval_time;
+            match result {
+                Object::Error(message) => write!(out, "{}", Diagnostic::new(message, span).render(input))?,
+                value => writeln!(out, "{}", value)?,
+            }
+        }
+        Err(errors) => write_parser_errors(input, &errors, out)?,
+    }
+    Ok(timings)
+}
+
+/// Reads the script at `path` and evaluates it against `env`, so that its
+/// bindings join the session. Unlike a line of input, the script's value
+/// isn't written; only its output and any errors are.
+pub fn load_file(path: &str, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> io::Result<()> {
+    load_file_timed(path, env, out).map(|_| ())
+}
+
+/// Like [`load_file`], also returning how long parsing and evaluating the
+/// script took, or `None` if it couldn't be read
+pub fn load_file_timed(path: &str, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> io::Result<Option<Timings>> {
+    let source = match crate::read_file(path) {
+        Ok(source) => source,
+        Err(err) => return writeln!(out, "{}", Object::Error(err.to_string())).map(|()| None),
+    };
+    let (parsed, parse_time) = timed(|| Parser::new(Lexer::new(&source)).parse_program());
+    let mut timings = Timings { parse: parse_time, ..Timings::default() };
+    match parsed {
+        Ok(program) => {
+            let ((result, span), eval_time) = timed(|| eval_program(program, env, out));
+            timings.eval = eval_time;
+            if let Object::Error(message) = result {
+                write!(out, "{}", Diagnostic::new(message, span).render(&source))?;
+            }
+        }
+        Err(errors) => write_parser_errors(&source, &errors, out)?,
+    }
+    Ok(Some(timings))
+}
+
+/// Defines and expands the program's macros, then evaluates it against `env`,
+/// returning any error with the span of the statement it came from
+fn eval_program(mut program: Program, env: &Rc<RefCell<Environment>>, out: &mut dyn Write) -> (Object, Option<Span>) {
+    macro_expansion::define_macros(&mut program, env);
+    match macro_expansion::expand_macros(program, env) {
+        Ok(program) => Evaluator::new(out).eval_located(&program, env),
+        Err(message) => (Object::Error(message), None),
+    }
+}
+
+/// Compiles a single line of input and runs it on a fresh VM, writing the
+/// last value popped off the stack
+pub fn vm_line(input: &str, out: &mut dyn Write) -> io::Result<()> {
+    vm_line_timed(input, out).map(|_| ())
+}
+
+/// Like [`vm_line`], also returning how long parsing took and how long
+/// compiling and running took, which counts as evaluation
+pub fn vm_line_timed(input: &str, out: &mut dyn Write) -> io::Result<Timings> {
+    let (parsed, parse_time) = timed(|| Parser::new(Lexer::new(input)).parse_program());
+    let mut timings = Timings { parse: parse_time, ..Timings::default() };
+    let program = match parsed {
+        Ok(program) => program,
+        Err(errors) => return write_parser_errors(input, &errors, out).map(|()| timings),
+    };
+    let (result, eval_time) = timed(|| {
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+        let mut vm = Vm::new(compiler.bytecode());
+        vm.run()?;
+        Ok::<_, String>(vm.last_popped().clone())
+    });
+    timings.eval = eval_time;
+    match result {
+        Ok(object) => writeln!(out, "{}", object)?,
+        Err(message) => writeln!(out, "{}", Object::Error(message))?,
+    }
+    Ok(timings)
+}
+
+/// Writes each binding as `name = value`, sorted by name, with values shown by
+/// `inspect`. Built-ins are only listed when `all` is set.
+pub fn write_env(env: &Environment, all: bool, out: &mut dyn Write) -> io::Result<()> {
+    let mut entries: Vec<(&str, String)> = env.iter().map(|(name, value)| (name, value.inspect())).collect();
+    if all {
+        entries.extend(builtins::names().filter_map(|name| Some((name, builtins::lookup(name)?.inspect()))));
+        entries.sort_by_key(|(name, _)| *name);
+    }
+    for (name, value) in entries {
+        writeln!(out, "{} = {}", name, value)?;
+    }
+    Ok(())
+}
+
+/// Renders each error under the source line it occurred on, with a caret at its column
+fn write_parser_errors(input: &str, errors: &[ParseError], out: &mut dyn Write) -> io::Result<()> {
+    for error in errors {
+        write!(out, "{}", Diagnostic::from(error).render(input))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn test_environment_persists_between_lines() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+
+        eval_line("let counter = 0;", &env, &mut out).unwrap();
+        eval_line("let add = fn(x) { counter + x };", &env, &mut out).unwrap();
+        eval_line("add(counter + 1)", &env, &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "null\nnull\n1\n");
+    }
+
+    #[test]
+    fn test_macros_persist_between_lines() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+
+        eval_line("let twice = macro(x) { quote(unquote(x) + unquote(x)) };", &env, &mut out).unwrap();
+        eval_line("twice(3 * 2)", &env, &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "null\n12\n");
+    }
+
+    #[test]
+    fn test_parser_errors_are_reported() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+
+        eval_line("let x 5; let y 10;", &env, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error: Expected Assign, got Int(5)\n \
+             --> 1:7\n  \
+             |\n\
+             1 | let x 5; let y 10;\n  \
+             |       ^\n\
+             error: Expected Assign, got Int(10)\n \
+             --> 1:16\n  \
+             |\n\
+             1 | let x 5; let y 10;\n  \
+             |                ^^\n"
+        );
+    }
+
+    #[test]
+    fn test_runtime_errors_are_reported() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+
+        eval_line("let x = 1; x + true; x", &env, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error: type mismatch: INTEGER + BOOLEAN\n \
+             --> 1:12\n  \
+             |\n\
+             1 | let x = 1; x + true; x\n  \
+             |            ^^^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_handle_line_modes() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let test_cases = vec![
+            (ReplMode::Eval, "1 + 2 * 3", "7\n"),
+            (ReplMode::Tokens, "1 + x", "Int(1)\nPlus\nIdent(\"x\")\n"),
+            (ReplMode::Ast, "1 + 2 * 3", "Program\n  ExpressionStatement\n    Infix +\n      Integer 1\n      Infix *\n        Integer 2\n        Integer 3\n"),
+            (ReplMode::Vm, "1 + 2 * 3", "7\n"),
+            (ReplMode::Vm, "1 / 0", "ERROR: division by zero\n"),
+        ];
+        for (mode, input, expected) in test_cases {
+            let mut out: Vec<u8> = Vec::new();
+            handle_line(mode, input, &env, &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_ast_mode_does_not_evaluate() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+
+        handle_line(ReplMode::Ast, "let x = 5;", &env, &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "Program\n  Let x\n    Integer 5\n");
+        assert_eq!(env.borrow().get("x"), None);
+    }
+
+    #[test]
+    fn test_repl_mode_from_flag() {
+        assert_eq!(ReplMode::from_flag("--tokens"), Some(ReplMode::Tokens));
+        assert_eq!(ReplMode::from_flag("--ast"), Some(ReplMode::Ast));
+        assert_eq!(ReplMode::from_flag("--eval"), Some(ReplMode::Eval));
+        assert_eq!(ReplMode::from_flag("script.mky"), None);
+    }
+
+    #[test]
+    fn test_write_env() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+        eval_line(r#"let name = "monkey"; let age = 3;"#, &env, &mut out).unwrap();
+
+        let mut out: Vec<u8> = Vec::new();
+        write_env(&env.borrow(), false, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "age = 3\nname = \"monkey\"\n");
+
+        let mut out: Vec<u8> = Vec::new();
+        write_env(&env.borrow(), true, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        let mut sorted = lines.clone();
+        sorted.sort();
+        assert_eq!(lines, sorted);
+        assert!(lines.contains(&"age = 3"));
+        assert!(lines.contains(&"len = builtin function"));
+        assert!(lines.contains(&"name = \"monkey\""));
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        let test_cases = vec![
+            ("let x = 5;", false),
+            ("let f = fn(x) {", true),
+            ("let f = fn(x) {\n  x + 1\n};", false),
+            ("add(1, [2, 3", true),
+            ("add(1, [2, 3])", false),
+            ("if (x) { \"}\" ", true),
+            ("let s = \"{\"; // (", false),
+            ("let x =", true),
+            ("}", false),
+            ("let x 5; fn() {", false),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(is_incomplete(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_read_load_command() {
+        let test_cases = vec![
+            (":load lib.mky\n", Command::Load("lib.mky".to_string())),
+            (":load  dir/my lib.mky \n", Command::Load("dir/my lib.mky".to_string())),
+            (":loader\n", Command::Eval),
+        ];
+        for (input, expected) in test_cases {
+            let mut reader = input.as_bytes();
+            let mut buf = String::new();
+            assert_eq!(read_command(&mut reader, &mut buf).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_load_file() {
+        let path = std::env::temp_dir().join(format!("monkey-rs-load-{}.mky", std::process::id()));
+        std::fs::write(&path, "let double = fn(x) { x * 2 };\nputs(\"loaded\");\ndouble(1)").unwrap();
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut out: Vec<u8> = Vec::new();
+
+        load_file(path.to_str().unwrap(), &env, &mut out).unwrap();
+        eval_line("double(21)", &env, &mut out).unwrap();
+        std::fs::write(&path, "let broken = ;").unwrap();
+        load_file(path.to_str().unwrap(), &env, &mut out).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        load_file(path.to_str().unwrap(), &env, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[..3], ["loaded", "42", "error: Unexpected token Semicolon"]);
+        assert!(lines.last().unwrap().starts_with("ERROR: could not read"), "{}", out);
+    }
+
+    #[test]
+    fn test_every_mode_and_load_are_timed() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        for mode in [ReplMode::Eval, ReplMode::Vm] {
+            let timings = handle_line_timed(mode, "let x = 2; x * 21", &env, &mut io::sink()).unwrap();
+            assert!(timings.parse > Duration::ZERO && timings.eval > Duration::ZERO, "{:?}", mode);
+        }
+        for mode in [ReplMode::Tokens, ReplMode::Ast] {
+            let timings = handle_line_timed(mode, "let x = 2; x * 21", &env, &mut io::sink()).unwrap();
+            assert!(timings.parse > Duration::ZERO && timings.eval == Duration::ZERO, "{:?}", mode);
+        }
+
+        let path = std::env::temp_dir().join(format!("monkey-rs-load-timed-{}.mky", std::process::id()));
+        std::fs::write(&path, "let y = 1;").unwrap();
+        let timings = load_file_timed(path.to_str().unwrap(), &env, &mut io::sink()).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(timings.parse > Duration::ZERO && timings.eval > Duration::ZERO);
+        assert_eq!(load_file_timed(path.to_str().unwrap(), &env, &mut io::sink()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_command_quits_on_eof() {
+        let mut reader: &[u8] = b"";
+        let mut buf = String::new();
+
+        assert_eq!(read_command(&mut reader, &mut buf).unwrap(), Command::Quit);
+    }
+
+    #[test]
+    fn test_read_command() {
+        let mut reader: &[u8] = b"let x = 5;\n:quit\n  exit  \n\n:tokens\n:ast\n:eval\n:engine vm\n:engine eval\n:env\n:env all\n:time on\n:time off\n";
+        let mut buf = String::new();
+        let expected = [
+            Command::Eval,
+            Command::Quit,
+            Command::Quit,
+            Command::Eval,
+            Command::SetMode(ReplMode::Tokens),
+            Command::SetMode(ReplMode::Ast),
+            Command::SetMode(ReplMode::Eval),
+            Command::SetMode(ReplMode::Vm),
+            Command::SetMode(ReplMode::Eval),
+            Command::ShowEnv { all: false },
+            Command::ShowEnv { all: true },
+            Command::SetTiming(true),
+            Command::SetTiming(false),
+            Command::Quit,
+        ];
+
+        for command in expected {
+            assert_eq!(read_command(&mut reader, &mut buf).unwrap(), command);
+            buf.clear();
+        }
+    }
+}
@@ -1,38 +1,59 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, PartialEq, Clone, Hash, Eq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Token {
-    Illegal,
+    Illegal(char), // the first character of input the lexer could not make sense of
+    IllegalEscape(char), // the character after the `\` of an unknown escape, e.g. `q` for "\q"
     EOF,
 
     // Identifiers + literals
     Ident(String), // add, foobar, x, y, ...
     Int(i64), // 1343456
+    IntTooLarge(String), // an integer literal that does not fit in an i64
+    Float(f64), // 3.14
+    Str(String), // "foobar"
+    Char(char), // 'a'
     Bool(bool), // true, false
+    Null,
 
     // Operators
     Assign,
+    PlusEq,
+    MinusEq,
+    AsteriskEq,
+    SlashEq,
     Plus,
 
     // Delimiters
     Comma,
     Semicolon,
+    Colon,
 
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
 
     // Operators
     Bang,
     Minus,
     Slash,
     Asterisk,
+    Pow,
+    Percent,
     Lt,
     Gt,
+    Le,
+    Ge,
     Eq,
     NotEq,
+    And,
+    Or,
     
 
     // Keywords
@@ -41,53 +62,95 @@ pub enum Token {
     If,
     Else,
     Return,
+    While,
+    Macro,
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            Token::Illegal => write!(f, "ILLEGAL"),
+            Token::Illegal(c) => write!(f, "ILLEGAL({:?})", c),
+            Token::IllegalEscape(c) => write!(f, "ILLEGAL(\"\\{}\")", c),
             Token::EOF => write!(f, "EOF"),
             Token::Ident(ident) => write!(f, "{}", ident),
             Token::Int(int) => write!(f, "{}", int),
+            Token::IntTooLarge(literal) => write!(f, "{}", literal),
+            Token::Float(float) => write!(f, "{:?}", float),
+            Token::Str(string) => write!(f, "{}", string),
+            Token::Char(c) => write!(f, "{}", c),
             Token::Bool(boolean) => write!(f, "{}", boolean),
+            Token::Null => write!(f, "null"),
             Token::Assign => write!(f, "="),
+            Token::PlusEq => write!(f, "+="),
+            Token::MinusEq => write!(f, "-="),
+            Token::AsteriskEq => write!(f, "*="),
+            Token::SlashEq => write!(f, "/="),
             Token::Plus => write!(f, "+"),
             Token::Comma => write!(f, ","),
             Token::Semicolon => write!(f, ";"),
+            Token::Colon => write!(f, ":"),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
             Token::LBrace => write!(f, "{{"),
             Token::RBrace => write!(f, "}}"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
             Token::Bang => write!(f, "!"),
             Token::Minus => write!(f, "-"),
             Token::Slash => write!(f, "/"),
             Token::Asterisk => write!(f, "*"),
+            Token::Pow => write!(f, "**"),
+            Token::Percent => write!(f, "%"),
             Token::Lt => write!(f, "<"),
             Token::Gt => write!(f, ">"),
+            Token::Le => write!(f, "<="),
+            Token::Ge => write!(f, ">="),
             Token::Eq => write!(f, "=="),
             Token::NotEq => write!(f, "!="),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
             Token::Function => write!(f, "fn"),
             Token::Let => write!(f, "let"),
             Token::If => write!(f, "if"),
             Token::Else => write!(f, "else"),
             Token::Return => write!(f, "return"),
+            Token::While => write!(f, "while"),
+            Token::Macro => write!(f, "macro"),
         }
     }
 
 }
 
 impl Token {
-    pub fn from_ident(ident: String) -> Token {
-        match ident.as_str() {
+    pub fn from_ident(ident: &str) -> Token {
+        match ident {
             "fn" => Token::Function,
             "let" => Token::Let,
             "true" => Token::Bool(true),
             "false" => Token::Bool(false),
+            "null" => Token::Null,
             "if" => Token::If,
             "else" => Token::Else,
             "return" => Token::Return,
+            "while" => Token::While,
+            "macro" => Token::Macro,
             _ => Token::Ident(ident.to_string()),
         }
     }
+
+    /// Whether this is a word the lexer reserves, which can't be used as a name
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            Token::Function
+                | Token::Let
+                | Token::Bool(_)
+                | Token::Null
+                | Token::If
+                | Token::Else
+                | Token::Return
+                | Token::While
+                | Token::Macro
+        )
+    }
 }
@@ -0,0 +1,197 @@
+use crate::compiler::{Bytecode, Instruction};
+use crate::object::Object;
+
+const STACK_SIZE: usize = 2048;
+
+/// Stack machine that executes compiled `Bytecode`
+pub struct Vm {
+    constants: Vec<Object>,
+    instructions: Vec<Instruction>,
+    stack: Vec<Object>,
+    /// The value most recently popped, i.e. the result of the last expression statement
+    last_popped: Object,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Self {
+        Self {
+            constants: bytecode.constants,
+            instructions: bytecode.instructions,
+            stack: Vec::with_capacity(STACK_SIZE),
+            last_popped: Object::Null,
+        }
+    }
+
+    pub fn last_popped(&self) -> &Object {
+        &self.last_popped
+    }
+
+    /// Executes every instruction, stopping at the first runtime error
+    pub fn run(&mut self) -> Result<(), String> {
+        for ip in 0..self.instructions.len() {
+            match self.instructions[ip] {
+                Instruction::Constant(index) => self.push(self.constants[index].clone())?,
+                Instruction::Pop => {
+                    self.last_popped = self.pop()?;
+                }
+                Instruction::True => self.push(Object::Boolean(true))?,
+                Instruction::False => self.push(Object::Boolean(false))?,
+                Instruction::Minus => match self.pop()? {
+                    Object::Integer(value) => {
+                        let value = value.checked_neg().ok_or("integer overflow")?;
+                        self.push(Object::Integer(value))?
+                    }
+                    other => return Err(format!("unknown operator: -{}", other.type_name())),
+                },
+                Instruction::Bang => {
+                    let operand = self.pop()?;
+                    self.push(Object::Boolean(!operand.is_truthy()))?
+                }
+                instruction => {
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    let result = binary_operation(instruction, left, right)?;
+                    self.push(result)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, object: Object) -> Result<(), String> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+        self.stack.push(object);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Object, String> {
+        self.stack.pop().ok_or_else(|| "stack underflow".to_string())
+    }
+}
+
+fn binary_operation(instruction: Instruction, left: Object, right: Object) -> Result<Object, String> {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => integer_operation(instruction, left, right),
+        (Object::Boolean(left), Object::Boolean(right)) => match instruction {
+            Instruction::Equal => Ok(Object::Boolean(left == right)),
+            Instruction::NotEqual => Ok(Object::Boolean(left != right)),
+            _ => Err(format!("unknown operator: BOOLEAN {} BOOLEAN", symbol(instruction))),
+        },
+        (left, right) if left.type_name() != right.type_name() => Err(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            symbol(instruction),
+            right.type_name()
+        )),
+        (left, right) => Err(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            symbol(instruction),
+            right.type_name()
+        )),
+    }
+}
+
+fn integer_operation(instruction: Instruction, left: i64, right: i64) -> Result<Object, String> {
+    let overflow = || "integer overflow".to_string();
+    let result = match instruction {
+        Instruction::Add => Object::Integer(left.checked_add(right).ok_or_else(overflow)?),
+        Instruction::Sub => Object::Integer(left.checked_sub(right).ok_or_else(overflow)?),
+        Instruction::Mul => Object::Integer(left.checked_mul(right).ok_or_else(overflow)?),
+        Instruction::Div if right == 0 => return Err("division by zero".to_string()),
+        Instruction::Div => Object::Integer(left.checked_div(right).ok_or_else(overflow)?),
+        Instruction::Mod if right == 0 => return Err("modulo by zero".to_string()),
+        Instruction::Mod => Object::Integer(left.checked_rem(right).ok_or_else(overflow)?),
+        Instruction::Equal => Object::Boolean(left == right),
+        Instruction::NotEqual => Object::Boolean(left != right),
+        Instruction::GreaterThan => Object::Boolean(left > right),
+        Instruction::GreaterEqual => Object::Boolean(left >= right),
+        _ => return Err(format!("unknown operator: INTEGER {} INTEGER", symbol(instruction))),
+    };
+    Ok(result)
+}
+
+/// The source operator an instruction was compiled from, for error messages
+fn symbol(instruction: Instruction) -> &'static str {
+    match instruction {
+        Instruction::Add => "+",
+        Instruction::Sub => "-",
+        Instruction::Mul => "*",
+        Instruction::Div => "/",
+        Instruction::Mod => "%",
+        Instruction::Equal => "==",
+        Instruction::NotEqual => "!=",
+        Instruction::GreaterThan => ">",
+        Instruction::GreaterEqual => ">=",
+        _ => "?",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::environment::Environment;
+    use crate::evaluator::eval;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn run_vm(input: &str) -> Object {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+        let mut vm = Vm::new(compiler.bytecode());
+        match vm.run() {
+            Ok(()) => vm.last_popped().clone(),
+            Err(message) => Object::Error(message),
+        }
+    }
+
+    fn run_evaluator(input: &str) -> Object {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        eval(&program, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    #[test]
+    fn test_vm_matches_evaluator() {
+        let programs = vec![
+            "1",
+            "1 + 2",
+            "1; 2",
+            "50 / 2 * 2 + 10 - 5",
+            "5 * (2 + 10)",
+            "-5 + 10 * -2",
+            "10 % 3",
+            "(5 + 10 * 2 + 15 / 3) * 2 + -10",
+            "true",
+            "!true",
+            "!!5",
+            "1 < 2",
+            "1 > 2",
+            "2 <= 2",
+            "3 >= 4",
+            "1 == 1",
+            "1 != 1",
+            "true == false",
+            "(1 < 2) == true",
+            "(1 > 2) != false",
+            "1 + true",
+            "true + false",
+            "-true",
+            "10 % 0",
+            "9223372036854775807 + 1",
+        ];
+        for input in programs {
+            assert_eq!(run_vm(input), run_evaluator(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(run_vm("1 / 0"), Object::Error("division by zero".to_string()));
+    }
+}